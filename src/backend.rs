@@ -0,0 +1,475 @@
+use crate::config::{Config, Template};
+use crate::dryrun;
+use crate::handoff;
+use crate::sessions::{self, SessionStatus};
+use crate::state;
+use crate::zellij::Zellij;
+use fork::{daemon, Fork};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicUsize;
+use std::thread;
+use std::time::{Duration, Instant};
+use zellij_utils::data::ClientId;
+
+// How long after starting our client we keep looking out for it to show
+// up in the session, to do what's to be done once it's in, and how often
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(10);
+const LOOK_EVERY: Duration = Duration::from_millis(100);
+
+/// What to do in a session once our client is in
+#[derive(Debug, Default, Clone)]
+pub struct AfterAttach {
+    /// The tab to go to, counting from 1
+    pub tab: Option<usize>,
+    /// Typed into the focused pane, with an Enter
+    pub run: Option<String>,
+}
+
+impl AfterAttach {
+    fn is_empty(&self) -> bool {
+        self.tab.is_none() && self.run.is_none()
+    }
+}
+
+/// Where sessions live: what's running, and starting, attaching to and
+/// killing them. The picker and the commands that start, attach to or kill
+/// sessions go through this, so that can be tried out against something
+/// other than real zellij servers; what zellij's CLI does inside a session
+/// (layouts, tabs, renaming) is still asked of `Zellij` directly.
+pub trait SessionBackend {
+    /// The running sessions
+    fn list(&self) -> io::Result<Vec<String>>;
+    /// How `session` is doing, or None if there's no such session
+    fn probe(&self, session: &str) -> Option<SessionStatus>;
+    /// Sessions that are there but can't be used, leaving out the `running`
+    /// ones already known to be fine
+    fn troubled(&self, running: &[String]) -> Vec<(String, SessionStatus)>;
+    /// Whether anyone's attached to `session`
+    fn is_attached(&self, session: &str) -> bool {
+        matches!(self.probe(session), Some(SessionStatus::Alive { clients }) if clients > 0)
+    }
+    /// Starts `session` without attaching, returning once it's up
+    fn create(&self, session: &str, template: &Template) -> io::Result<()>;
+    /// Hands the terminal over to `session` (see `handoff::attach`), unless
+    /// a signal is `pending` first, doing what's `after` once the client is
+    /// in
+    fn attach(
+        &self,
+        session: &str,
+        after: &AfterAttach,
+        pending: &AtomicUsize,
+    ) -> io::Result<handoff::Outcome>;
+    fn kill(&self, session: &str) -> io::Result<()>;
+    /// Kicks every client off `session`, so whoever attaches next is alone
+    fn detach_others(&self, session: &str) -> io::Result<()>;
+    /// Where the locks taken by `create_unless_running` are kept
+    fn lock_dir(&self) -> Option<PathBuf> {
+        state::runtime_dir().map(|dir| dir.join("locks"))
+    }
+}
+
+/// The sessions of the zellij servers in `socket_dir`, talked to over IPC
+/// and through the zellij CLI
+pub struct ZellijBackend<'a> {
+    zellij: &'a Zellij,
+    socket_dir: &'a Path,
+    config: &'a Config,
+}
+
+impl<'a> ZellijBackend<'a> {
+    pub fn new(zellij: &'a Zellij, socket_dir: &'a Path, config: &'a Config) -> Self {
+        ZellijBackend {
+            zellij,
+            socket_dir,
+            config,
+        }
+    }
+
+    fn pretend_to_attach(&self, session: &str, status: Option<SessionStatus>, after: &AfterAttach) {
+        let status = match status {
+            Some(SessionStatus::Alive { clients }) => format!("alive, {} client(s)", clients),
+            Some(SessionStatus::DeadSocketRemoved) | None => {
+                "no such session, `attach -c` creates it".to_string()
+            }
+            Some(status) => status.trouble().unwrap_or_default().to_string(),
+        };
+        let mut cmd = self.zellij.command();
+        cmd.arg("attach").arg("-c").arg(session);
+        dryrun::pretend(format_args!(
+            "attach to {} (socket {}: {}) by running {:?}",
+            session,
+            self.socket_dir.join(session).display(),
+            status,
+            cmd
+        ));
+        if let Some(tab) = after.tab {
+            dryrun::pretend(format_args!("then go to tab {}", tab));
+        }
+        if let Some(command) = &after.run {
+            dryrun::pretend(format_args!("then run {:?} in it", command));
+        }
+    }
+
+    // Does what's `after` once our client is in: once a client the session
+    // didn't have `before` shows up, or it has more than it had (zellij
+    // hands a detached client's ID to the next one to attach). Gives up if
+    // that takes too long.
+    //
+    // Someone else attaching at the same moment can still be taken for us,
+    // and someone leaving just as we arrive can hide us until the timeout;
+    // `before` is taken right before our client starts to keep that window
+    // small. There's no asking zellij which client is ours.
+    fn once_attached(&self, session: &str, after: &AfterAttach, before: Vec<ClientId>) {
+        let zellij = self.zellij.clone();
+        let socket_dir = self.socket_dir.to_path_buf();
+        let session = session.to_string();
+        let after = after.clone();
+        thread::spawn(move || {
+            let deadline = Instant::now() + ATTACH_TIMEOUT;
+            while Instant::now() < deadline {
+                match sessions::client_ids(&socket_dir, &session) {
+                    Ok(clients)
+                        if clients.len() > before.len()
+                            || clients.iter().any(|id| !before.contains(id)) =>
+                    {
+                        if let Some(tab) = after.tab {
+                            log::info!("Going to tab {} of {:?}", tab, session);
+                            if let Err(err) = zellij.go_to_tab(&session, tab) {
+                                log::warn!("Couldn't go to tab {} of {:?}: {}", tab, session, err);
+                            }
+                        }
+                        if let Some(command) = &after.run {
+                            log::info!("Running {:?} in {:?}", command, session);
+                            if let Err(err) =
+                                zellij.write_chars(&session, &format!("{}\n", command))
+                            {
+                                log::warn!("Couldn't run {:?} in {:?}: {}", command, session, err);
+                            }
+                        }
+                        return;
+                    }
+                    _ => thread::sleep(LOOK_EVERY),
+                }
+            }
+            log::warn!(
+                "Our client never showed up in {:?}, did nothing in it",
+                session
+            );
+        });
+    }
+}
+
+impl SessionBackend for ZellijBackend<'_> {
+    fn list(&self) -> io::Result<Vec<String>> {
+        sessions::get_sessions(self.socket_dir).map_err(io::Error::from)
+    }
+
+    fn probe(&self, session: &str) -> Option<SessionStatus> {
+        sessions::probe(self.socket_dir, session)
+    }
+
+    fn troubled(&self, running: &[String]) -> Vec<(String, SessionStatus)> {
+        sessions::troubled(self.socket_dir, running)
+    }
+
+    fn create(&self, session: &str, template: &Template) -> io::Result<()> {
+        crate::spawn(self.zellij, self.socket_dir, self.config, session, template)
+    }
+
+    fn attach(
+        &self,
+        session: &str,
+        after: &AfterAttach,
+        pending: &AtomicUsize,
+    ) -> io::Result<handoff::Outcome> {
+        if dryrun::is_on() {
+            self.pretend_to_attach(session, self.probe(session), after);
+            return Ok(handoff::Outcome::HandedOff);
+        }
+        // The tricky part here is that we don't want to occupy
+        // two entire processes, where one of them is a deadbeat parent
+        // So, my idea here is to fork into a daemon, but preserve all the
+        // relevant pipes
+        let fork = || {
+            // Staying put matters when `attach -c` ends up creating the
+            // session: moving to / would start it there
+            match daemon(
+                /* nochdir: bool = */ true, /* noclose: bool = */ true,
+            ) {
+                Ok(Fork::Child) => {
+                    log::debug!("Daemonized as pid {}", std::process::id());
+                    Ok(true)
+                }
+                // The intermediate process of the double fork; its job is done
+                Ok(Fork::Parent(pid)) => {
+                    log::debug!("Handed off to pid {}", pid);
+                    Ok(false)
+                }
+                Err(errno) => Err(io::Error::other(format!(
+                    "couldn't daemonize (errno {})",
+                    errno
+                ))),
+            }
+        };
+        // Opting to use `.spawn()` since it inherits the pipes
+        // Otherwise, `.output()` would create new ones and detach
+        let spawn = || {
+            // Whoever's attached already, to tell our client apart from
+            let before = if after.is_empty() {
+                Vec::new()
+            } else {
+                sessions::client_ids(self.socket_dir, session).unwrap_or_default()
+            };
+            let child = self
+                .zellij
+                .command()
+                .arg("attach")
+                .arg("-c")
+                .arg(session)
+                .spawn()?;
+            // Only now: threads don't make it through the fork
+            if !after.is_empty() {
+                self.once_attached(session, after, before);
+            }
+            Ok(child)
+        };
+        handoff::attach(pending, fork, spawn)
+    }
+
+    fn kill(&self, session: &str) -> io::Result<()> {
+        self.zellij.kill_session(session)
+    }
+
+    fn detach_others(&self, session: &str) -> io::Result<()> {
+        sessions::detach_other_clients(self.socket_dir, session)
+    }
+}
+
+/// Creates `session` unless it's running by the time it's our turn: of two
+/// choosers creating the same name at once, the second leaves it to the
+/// first and attaches to its session. Whether we were the ones to create it.
+pub fn create_unless_running(
+    backend: &dyn SessionBackend,
+    session: &str,
+    template: &Template,
+) -> io::Result<bool> {
+    sessions::check_name(session)?;
+    let _lock = backend
+        .lock_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "nowhere to keep locks"))
+        .and_then(|dir| {
+            sessions::lock_creation(&dir, session, || {
+                eprintln!("\"{}\" is being created elsewhere, waiting...", session)
+            })
+        })
+        .inspect_err(|err| log::warn!("Creating {:?} without a lock: {}", session, err))
+        .ok();
+    if backend.probe(session).is_some_and(SessionStatus::is_alive) {
+        log::info!("{:?} was created in the meantime", session);
+        return Ok(false);
+    }
+    backend.create(session, template)?;
+    Ok(true)
+}
+
+/// Sessions that only exist in memory, for tests
+#[cfg(test)]
+pub mod mock {
+    use super::{AfterAttach, SessionBackend};
+    use crate::config::Template;
+    use crate::handoff;
+    use crate::sessions::SessionStatus;
+    use std::io;
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::PathBuf;
+    use std::process::ExitStatus;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    pub struct MemoryBackend {
+        pub running: Mutex<Vec<String>>,
+        /// The running sessions someone's attached to
+        pub attached: Vec<String>,
+        /// Sessions there are sockets for, but no talking to
+        pub troubled: Vec<(String, SessionStatus)>,
+        /// Every create, attach and detach (and what was done once
+        /// attached), in order, like `create work`
+        pub calls: Mutex<Vec<String>>,
+        /// How long a create takes, to give races a chance
+        pub create_delay: Duration,
+    }
+
+    impl MemoryBackend {
+        pub fn with(running: &[&str]) -> Self {
+            MemoryBackend {
+                running: Mutex::new(running.iter().map(|s| s.to_string()).collect()),
+                ..MemoryBackend::default()
+            }
+        }
+
+        fn call(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    impl SessionBackend for MemoryBackend {
+        fn list(&self) -> io::Result<Vec<String>> {
+            Ok(self.running.lock().unwrap().clone())
+        }
+
+        fn probe(&self, session: &str) -> Option<SessionStatus> {
+            let running = self.running.lock().unwrap();
+            let clients = self.attached.iter().filter(|s| *s == session).count();
+            running
+                .iter()
+                .any(|s| s == session)
+                .then_some(SessionStatus::Alive { clients })
+        }
+
+        fn troubled(&self, running: &[String]) -> Vec<(String, SessionStatus)> {
+            self.troubled
+                .iter()
+                .filter(|(session, _)| !running.contains(session))
+                .cloned()
+                .collect()
+        }
+
+        fn create(&self, session: &str, _: &Template) -> io::Result<()> {
+            self.call(format!("create {}", session));
+            thread::sleep(self.create_delay);
+            let mut running = self.running.lock().unwrap();
+            if running.iter().any(|s| s == session) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} is already running", session),
+                ));
+            }
+            running.push(session.to_string());
+            Ok(())
+        }
+
+        fn attach(
+            &self,
+            session: &str,
+            after: &AfterAttach,
+            pending: &AtomicUsize,
+        ) -> io::Result<handoff::Outcome> {
+            if pending.load(Ordering::SeqCst) != 0 {
+                return Ok(handoff::Outcome::Cancelled(handoff::Phase::Selected));
+            }
+            // Like `attach -c`
+            if self.probe(session).is_none() {
+                self.running.lock().unwrap().push(session.to_string());
+            }
+            self.call(format!("attach {}", session));
+            if let Some(tab) = after.tab {
+                self.call(format!("tab {}", tab));
+            }
+            if let Some(command) = &after.run {
+                self.call(format!("run {}", command));
+            }
+            Ok(handoff::Outcome::Finished(ExitStatus::from_raw(0)))
+        }
+
+        fn kill(&self, session: &str) -> io::Result<()> {
+            let mut running = self.running.lock().unwrap();
+            let before = running.len();
+            running.retain(|s| s != session);
+            if running.len() == before {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} isn't running", session),
+                ));
+            }
+            Ok(())
+        }
+
+        fn detach_others(&self, session: &str) -> io::Result<()> {
+            self.call(format!("detach {}", session));
+            Ok(())
+        }
+
+        // Out of the way of a real chooser's locks; names still have to
+        // differ between tests running at once
+        fn lock_dir(&self) -> Option<PathBuf> {
+            let dir = format!("zellij-chooser-test-locks-{}", std::process::id());
+            Some(std::env::temp_dir().join(dir))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MemoryBackend;
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    // Lock files are per name, so tests running at once mustn't share one
+    fn unique(name: &str) -> String {
+        format!("{}-{}", name, std::process::id())
+    }
+
+    #[test]
+    fn creates_what_isnt_running() {
+        let backend = MemoryBackend::with(&["work"]);
+        let session = unique("fresh");
+        let created = create_unless_running(&backend, &session, &Template::default()).unwrap();
+        assert!(created);
+        assert!(backend.probe(&session).is_some());
+    }
+
+    #[test]
+    fn leaves_what_is_running_alone() {
+        let session = unique("running");
+        let backend = MemoryBackend::with(&[session.as_str()]);
+        let created = create_unless_running(&backend, &session, &Template::default()).unwrap();
+        assert!(!created);
+        assert!(backend.calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn of_two_creating_the_same_name_only_one_does() {
+        let backend = Arc::new(MemoryBackend {
+            create_delay: Duration::from_millis(200),
+            ..MemoryBackend::default()
+        });
+        let session = unique("raced");
+        let racers: Vec<_> = (0..2)
+            .map(|_| {
+                let backend = Arc::clone(&backend);
+                let session = session.clone();
+                thread::spawn(move || {
+                    create_unless_running(backend.as_ref(), &session, &Template::default())
+                })
+            })
+            .collect();
+        let created: Vec<bool> = racers
+            .into_iter()
+            .map(|racer| racer.join().unwrap().unwrap())
+            .collect();
+        assert_eq!(created.iter().filter(|created| **created).count(), 1);
+        assert_eq!(
+            *backend.calls.lock().unwrap(),
+            [format!("create {}", session)]
+        );
+    }
+
+    #[test]
+    fn names_that_arent_a_file_name_are_refused() {
+        let backend = MemoryBackend::default();
+        for session in ["work/api", "../escaped", "..", "nul\0here", ""] {
+            let err = create_unless_running(&backend, session, &Template::default()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{:?}", session);
+        }
+        assert!(backend.calls.lock().unwrap().is_empty());
+        let locks = backend.lock_dir().unwrap();
+        assert!(!locks.join("work").exists());
+        assert!(!locks.parent().unwrap().join("escaped.lock").exists());
+    }
+}
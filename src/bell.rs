@@ -0,0 +1,66 @@
+use crate::config::Alert;
+use crate::dryrun;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+const FLASH_LENGTH: Duration = Duration::from_millis(100);
+
+/// Rings or flashes the terminal, if stderr is one, or says `what` happened
+/// in a desktop notification
+pub fn alert(alert: Alert, what: &str) {
+    if alert == Alert::Desktop {
+        // Not everyone has a notification daemon, which is nothing to fuss
+        // about
+        let sent = Command::new("notify-send")
+            .arg("zellij-chooser")
+            .arg(what)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if let Err(err) = sent {
+            log::warn!("Couldn't run notify-send: {}", err);
+        }
+        return;
+    }
+    let mut stderr = io::stderr();
+    if alert == Alert::Off || !stderr.is_terminal() {
+        return;
+    }
+    let _ = match alert {
+        Alert::Off | Alert::Desktop => Ok(()),
+        Alert::Bell => write!(stderr, "\x07"),
+        Alert::Flash => {
+            let _ = write!(stderr, "\x1b[?5h");
+            let _ = stderr.flush();
+            thread::sleep(FLASH_LENGTH);
+            write!(stderr, "\x1b[?5l")
+        }
+        // OSC 9, which iTerm2, WezTerm, Windows Terminal, foot and others
+        // turn into a desktop notification
+        Alert::Notify => write!(stderr, "\x1b]9;{}\x07", what),
+    };
+    let _ = stderr.flush();
+}
+
+/// Titles the terminal after `session`, keeping the title it had to put
+/// back with `restore_title`
+pub fn set_title(session: &str) {
+    let mut stderr = io::stderr();
+    if !stderr.is_terminal() || dryrun::is_on() {
+        return;
+    }
+    let _ = write!(stderr, "\x1b[22;0t\x1b]2;{}\x07", session);
+    let _ = stderr.flush();
+}
+
+pub fn restore_title() {
+    let mut stderr = io::stderr();
+    if !stderr.is_terminal() || dryrun::is_on() {
+        return;
+    }
+    let _ = write!(stderr, "\x1b[23;0t");
+    let _ = stderr.flush();
+}
@@ -0,0 +1,466 @@
+use crate::listing::{Filter, GroupBy, SortKey};
+use clap::{Parser, Subcommand};
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug, Clone)]
+#[clap(name = "zellij-chooser", version, about)]
+#[clap(
+    after_help = "Anything after `--` is handed to zellij verbatim, e.g.\n    zellij-chooser work -- --config ~/alt.kdl --debug"
+)]
+pub struct CliArgs {
+    /// Name of the session to attach to, or an unambiguous prefix of one
+    /// (it is created if nothing matches)
+    pub session: Option<String>,
+
+    /// Only attach to a session named exactly SESSION, never to one it's a
+    /// prefix of
+    #[clap(long, requires = "session")]
+    pub exact: bool,
+
+    /// Match SESSION fuzzily against the running sessions rather than as a
+    /// prefix, choosing between them in the picker when there are several
+    #[clap(long, requires = "session", conflicts_with = "exact")]
+    pub fuzzy_arg: bool,
+
+    /// Detach every other client connected to the session before attaching,
+    /// e.g. when it's stuck "in use" by a dead SSH connection
+    #[clap(long)]
+    pub detach_others: bool,
+
+    /// Once a running session is chosen, ask which of its tabs to attach
+    /// to (a template can ask for this with `pick_tab = true`)
+    #[clap(long)]
+    pub pick_tab: bool,
+
+    /// Attach to this tab of the session, by name or number
+    #[clap(long, value_name = "TAB", conflicts_with = "pick-tab")]
+    pub tab: Option<String>,
+
+    /// The zellij binary to run [default: `zellij_bin` from the config, or
+    /// `zellij` from $PATH]
+    #[clap(long, env = "ZELLIJ_CHOOSER_BIN", value_name = "PATH")]
+    pub zellij_bin: Option<PathBuf>,
+
+    /// Base directory zellij keeps its sockets in (sessions live in a
+    /// per-version subdirectory of it)
+    #[clap(long, env = "ZELLIJ_SOCKET_DIR", value_name = "PATH")]
+    pub socket_dir: Option<PathBuf>,
+
+    /// Carry on even though the zellij binary isn't the version the chooser
+    /// was built against (its IPC messages may be misunderstood)
+    #[clap(long)]
+    pub ignore_version_mismatch: bool,
+
+    /// Read the config from this file instead of
+    /// `$XDG_CONFIG_HOME/zellij-chooser/config.toml`
+    #[clap(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Attach to the session best (fuzzy) matching NAME, or create NAME
+    /// from the default template if nothing matches
+    #[clap(long, value_name = "NAME", conflicts_with_all = &["session", "query", "filter-last"])]
+    pub select_or_create: Option<String>,
+
+    /// Open the picker already filtered with this query, attaching right
+    /// away if exactly one session matches it
+    #[clap(long, value_name = "TEXT", conflicts_with = "filter-last")]
+    pub query: Option<String>,
+
+    /// Open the picker already filtered with the last query typed into it
+    #[clap(long)]
+    pub filter_last: bool,
+
+    /// Don't prompt, take whatever would be at the top of the picker's list
+    /// (after --query, --sort and --filter)
+    #[clap(long, conflicts_with_all = &["session", "select-or-create", "last"])]
+    pub first: bool,
+
+    /// Don't prompt, take whatever would be at the bottom of the picker's
+    /// list
+    #[clap(long, conflicts_with_all = &["session", "select-or-create"])]
+    pub last: bool,
+
+    /// Open the picker without the banner above it, or the reminders about
+    /// idle and untracked sessions
+    #[clap(short, long)]
+    pub quiet: bool,
+
+    /// Pick from the session names on stdin (one per line) instead of the
+    /// sessions running here, e.g. `ssh host zellij-chooser list |
+    /// zellij-chooser --stdin --print`
+    #[clap(long)]
+    pub stdin: bool,
+
+    /// Don't attach: print the chosen session's name to stdout (the picker
+    /// is still drawn on the terminal when stdout is captured)
+    #[clap(long, conflicts_with = "export-env")]
+    pub print: bool,
+
+    /// Don't attach: start $SHELL with ZELLIJ_CHOOSER_SELECTION set to the
+    /// chosen session's name instead
+    #[clap(long)]
+    pub export_env: bool,
+
+    /// List the sessions named in this file (one per line) first, in the
+    /// file's order
+    #[clap(long, value_name = "PATH")]
+    pub manifest: Option<PathBuf>,
+
+    /// Pick from (and attach to) the zellij sessions on another machine,
+    /// over SSH; `list` works too
+    #[clap(long, value_name = "USER@HOST", conflicts_with_all = &["stdin", "demo", "export-env"])]
+    pub remote: Option<String>,
+
+    /// Take a guided tour of the picker with a few throwaway sessions
+    #[clap(long, conflicts_with_all = &["session", "select-or-create", "query"])]
+    pub demo: bool,
+
+    /// Show how every session's rank was computed in the picker
+    #[clap(long)]
+    pub explain_ranking: bool,
+
+    /// Don't preview the top match's tabs while the list is narrowed down
+    /// (Alt-P still previews on demand)
+    #[clap(long)]
+    pub no_preview: bool,
+
+    /// Don't color the output (setting NO_COLOR does the same)
+    #[clap(long, global = true)]
+    pub no_color: bool,
+
+    /// Order `list` and the picker's initial list by this instead of rank
+    #[clap(long, value_enum, value_name = "KEY", global = true)]
+    pub sort: Option<SortKey>,
+
+    /// Split the picker's list up under headings by this
+    #[clap(long, value_enum, value_name = "KEY")]
+    pub group_by: Option<GroupBy>,
+
+    /// Only show sessions matching this glob (`ci-*`) or regex between
+    /// slashes (`/^ci-\d+$/`), in `list` and the picker
+    #[clap(long, value_name = "PATTERN", global = true)]
+    pub filter: Option<Filter>,
+
+    /// Log what we're doing (probing sockets, spawning, attaching) to
+    /// stderr, or once attached, to the log file in the state directory
+    #[clap(short, long, global = true)]
+    pub verbose: bool,
+
+    /// Like --verbose, but also log the IPC traffic and every command run
+    #[clap(long, global = true)]
+    pub debug: bool,
+
+    /// Pretend it's this moment (Unix seconds) when ranking and aging
+    /// sessions, to reproduce what someone else saw
+    #[clap(long, env = "ZELLIJ_CHOOSER_NOW", value_name = "SECS", global = true)]
+    pub now: Option<u64>,
+
+    /// Seed whatever is picked at random, so it's picked the same way every
+    /// run (--demo always does)
+    #[clap(long, env = "ZELLIJ_CHOOSER_SEED", value_name = "N", global = true)]
+    pub seed: Option<u64>,
+
+    /// Don't start, attach to, kill or change anything: print what would
+    /// be done instead (the zellij commands, hooks, sockets), going by what
+    /// the sessions look like now. `rename` prints its table, `import-tmux`
+    /// its layouts.
+    #[clap(long, global = true)]
+    pub dry_run: bool,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Everything after the first `--`, meant for zellij
+    #[clap(skip)]
+    pub zellij_args: Vec<OsString>,
+}
+
+impl CliArgs {
+    /// Parses our own arguments, setting everything after the first `--`
+    /// aside so it works the same with or without a subcommand
+    pub fn from_env() -> Self {
+        let mut args: Vec<OsString> = env::args_os().collect();
+        let zellij_args = match args.iter().position(|arg| arg == "--") {
+            Some(idx) => args.split_off(idx).into_iter().skip(1).collect(),
+            None => Vec::new(),
+        };
+        let mut opts = Self::parse_from(args);
+        opts.zellij_args = zellij_args;
+        opts
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SetAction {
+    /// Save the sessions named (or `@SET`s), or else the running ones
+    /// matching --filter, as a set; replaces any set by that name
+    Save { name: String, sessions: Vec<String> },
+    /// Print each set and its sessions
+    List,
+    /// Forget a set (the sessions are left alone)
+    Delete { name: String },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Attach to a session, landing on one of its tabs with --tab; the same
+    /// as `zellij-chooser SESSION --tab TAB`
+    Attach {
+        /// Name of the session, or an unambiguous prefix of one (it is
+        /// created if nothing matches)
+        session: String,
+
+        /// The tab to land on, by name or number
+        #[clap(long, value_name = "TAB")]
+        tab: Option<String>,
+    },
+    /// Create a session, then attach to it unless --detached is given
+    New {
+        /// Name of the new session (a made-up one, like `brisk-otter`, if
+        /// left out)
+        name: Option<String>,
+
+        /// Return as soon as the session is up instead of attaching,
+        /// e.g. to pre-warm a few sessions at login
+        #[clap(long)]
+        detached: bool,
+
+        /// Start from one of the `[templates.*]` in the config
+        #[clap(long, value_name = "NAME")]
+        template: Option<String>,
+
+        /// Start with this zellij layout (overrides the template's)
+        #[clap(long, value_name = "LAYOUT")]
+        layout: Option<String>,
+
+        /// Start the session in this directory rather than the current one
+        /// (overrides the template's)
+        #[clap(long, value_name = "PATH")]
+        cwd: Option<PathBuf>,
+    },
+    /// Rename a running session, carrying its tags, notes and history along;
+    /// or many at once with --match and --replace
+    Rename {
+        #[clap(required_unless_present = "pattern", conflicts_with = "pattern")]
+        from: Option<String>,
+        #[clap(required_unless_present = "pattern")]
+        to: Option<String>,
+
+        /// Rename every running session matching this glob (`old-*`) or
+        /// regex between slashes (`/^old-(\d+)$/`)
+        #[clap(long = "match", value_name = "PATTERN", requires = "replace")]
+        pattern: Option<Filter>,
+
+        /// New names for --match: `*`/`?` reuse what the glob's wildcards
+        /// matched (`new-*`), a regex can use `$1` and friends
+        #[clap(long, value_name = "TEMPLATE", requires = "pattern")]
+        replace: Option<String>,
+    },
+    /// Attach tags, a note or a working directory to a session; the picker
+    /// matches against all of them
+    Annotate {
+        session: String,
+
+        /// Add a tag (repeatable)
+        #[clap(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Remove a tag (repeatable)
+        #[clap(long = "untag", value_name = "TAG")]
+        untags: Vec<String>,
+
+        /// Replace the session's note; an empty string clears it
+        #[clap(long)]
+        note: Option<String>,
+
+        /// Record the directory the session is about
+        #[clap(long, value_name = "PATH")]
+        cwd: Option<PathBuf>,
+    },
+    /// Start keeping track of sessions started outside the chooser, noting
+    /// the directory their server runs in as their cwd
+    Adopt {
+        /// Which ones; every running session not tracked yet if left out
+        sessions: Vec<String>,
+
+        /// Tag them (repeatable)
+        #[clap(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Add them to a set (repeatable)
+        #[clap(long = "set", value_name = "SET")]
+        sets: Vec<String>,
+    },
+    /// Print the running sessions, one per line
+    List {
+        /// Also print exited sessions zellij can resurrect, marked "(dead)"
+        #[clap(long)]
+        include_dead: bool,
+
+        /// Print a JSON snapshot (names, status, start times) instead, for
+        /// scripts or for `diff` to compare against later
+        #[clap(long)]
+        json: bool,
+
+        /// Keep the list on screen, redrawn whenever a session starts or
+        /// goes away, until Ctrl-C
+        #[clap(long, conflicts_with = "json")]
+        watch: bool,
+    },
+    /// Report which sessions were created, killed or renamed since a
+    /// snapshot saved with `list --json`
+    Diff {
+        #[clap(value_name = "SNAPSHOT")]
+        snapshot: PathBuf,
+    },
+    /// Print sessions being created, renamed, killed, attached to and
+    /// detached from as it happens, until Ctrl-C
+    Events {
+        /// Seconds between looks at the sessions
+        #[clap(long, value_name = "SECS", default_value = "1")]
+        interval: u64,
+
+        /// Print each event as a line of JSON, for tools to read
+        #[clap(long)]
+        json: bool,
+    },
+    /// Keep a table of the sessions up to date and answer queries about it
+    /// on a local socket, one line of JSON each way, so status bars needn't
+    /// probe every session themselves
+    Daemon {
+        /// The socket to listen on [default:
+        /// `$XDG_RUNTIME_DIR/zellij-chooser.sock`]
+        #[clap(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+
+        /// Seconds between refreshes when nothing has started or gone away
+        #[clap(long, value_name = "SECS", default_value = "2")]
+        interval: u64,
+
+        /// Ask the daemon already running instead, printing its answer:
+        /// `sessions`, `summary`, or a request as JSON
+        #[clap(long, value_name = "REQUEST")]
+        query: Option<String>,
+    },
+    /// Show the running sessions by how much CPU and memory they and
+    /// everything in them use, refreshing until Ctrl-C
+    Top {
+        /// Seconds between refreshes
+        #[clap(long, value_name = "SECS", default_value = "2")]
+        interval: u64,
+
+        /// Print the table once instead of refreshing
+        #[clap(long)]
+        once: bool,
+    },
+    /// Move one of a session's tabs over to another session. Only its layout
+    /// moves: whatever ran in its panes is started afresh in the new one.
+    MoveTab {
+        from: String,
+        to: String,
+
+        /// The tab's name or number (asks, showing the tabs, if left out)
+        #[clap(long, value_name = "TAB")]
+        tab: Option<String>,
+
+        /// Leave the tab in FROM as well
+        #[clap(long)]
+        copy: bool,
+    },
+    /// Turn one of a session's tabs into a session of its own, started in the
+    /// background. As with move-tab, its commands start over.
+    BreakTab {
+        session: String,
+        /// Name of the new session
+        new_session: String,
+
+        /// The tab's name or number (asks, showing the tabs, if left out)
+        #[clap(long, value_name = "TAB")]
+        tab: Option<String>,
+
+        /// Leave the tab in SESSION as well
+        #[clap(long)]
+        copy: bool,
+    },
+    /// Start a copy of a session in the background, laid out like it is
+    /// now (or was, if it exited), with its tags and template
+    Clone {
+        source: String,
+        /// Name of the copy; SOURCE-2 (or -3, and so on) if left out
+        name: Option<String>,
+    },
+    /// List the sessions nobody has attached to in a while, oldest first
+    Prune {
+        /// Go through them, asking whether to kill each (or purge it, if
+        /// it has exited)
+        #[clap(long)]
+        interactive: bool,
+
+        /// How many days count as a while [default: `after_days` in the
+        /// config's `[idle]`]
+        #[clap(long, value_name = "DAYS")]
+        days: Option<u64>,
+    },
+    /// Look over zellij and its socket directory for what would keep
+    /// sessions from showing up, offering to fix what can be fixed (create
+    /// the directory, tighten its permissions, remove what isn't a live
+    /// session's socket)
+    Doctor {
+        /// Fix whatever can be without asking
+        #[clap(long)]
+        yes: bool,
+    },
+    /// Print how often and for how long each session has been attached to,
+    /// the most used first
+    Stats {
+        /// Print JSON instead, for scripts
+        #[clap(long)]
+        json: bool,
+    },
+    /// Recreate running tmux sessions (or just the ones named) as zellij
+    /// sessions, one tab per window with the same splits and directories
+    ImportTmux { sessions: Vec<String> },
+    /// Save, list or delete named sets of sessions, which `kill` and `open`
+    /// take as `@NAME`
+    Set {
+        #[clap(subcommand)]
+        action: SetAction,
+    },
+    /// Kill running sessions, by name or `@SET` (or `@GROUP`)
+    Kill {
+        #[clap(required = true)]
+        sessions: Vec<String>,
+    },
+    /// Start whichever of these sessions (by name, `@SET` or `@GROUP`) aren't
+    /// running, in the background
+    Open {
+        #[clap(required = true)]
+        sessions: Vec<String>,
+
+        /// Start new ones from this `[templates.*]` entry
+        #[clap(long, value_name = "NAME")]
+        template: Option<String>,
+    },
+    /// Start every session in one of the config's `[groups]` that isn't
+    /// running yet, in the background
+    Up {
+        group: String,
+
+        /// Start them from this `[templates.*]` entry
+        #[clap(long, value_name = "NAME")]
+        template: Option<String>,
+    },
+    /// Kill every running session in one of the config's `[groups]`
+    Down { group: String },
+    /// Delete what zellij saved to resurrect an exited session
+    Purge {
+        #[clap(required_unless_present = "all-dead")]
+        name: Option<String>,
+
+        /// Purge every exited session
+        #[clap(long, conflicts_with = "name")]
+        all_dead: bool,
+    },
+}
@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where the time of day comes from, for anything that ranks or ages
+/// sessions by it
+pub trait Clock: Send + Sync {
+    /// Unix seconds
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// Always the same moment, so rankings and ages come out the same on every
+/// run
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Where anything picked at random comes from
+pub trait Rng: Send + Sync {
+    fn next_u64(&self) -> u64;
+}
+
+/// SplitMix64: nothing to write home about, but plenty for names, and the
+/// same seed always gives the same sequence
+pub struct SeededRng(AtomicU64);
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng(AtomicU64::new(seed))
+    }
+
+    // Different on every run, and between runs started at the same moment
+    fn unseeded() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        SeededRng::new(nanos ^ (std::process::id() as u64).rotate_left(32))
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_u64(&self) -> u64 {
+        let mut z = self
+            .0
+            .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+static CLOCK: OnceLock<Box<dyn Clock>> = OnceLock::new();
+static RNG: OnceLock<Box<dyn Rng>> = OnceLock::new();
+
+/// Settles the time and randomness for the rest of the run: the real ones,
+/// unless `now` pins the time or `seed` the random choices
+pub fn init(now: Option<u64>, seed: Option<u64>) {
+    let clock: Box<dyn Clock> = match now {
+        Some(now) => Box::new(FixedClock(now)),
+        None => Box::new(SystemClock),
+    };
+    let rng = match seed {
+        Some(seed) => SeededRng::new(seed),
+        None => SeededRng::unseeded(),
+    };
+    let _ = CLOCK.set(clock);
+    let _ = RNG.set(Box::new(rng));
+}
+
+/// The current time, in Unix seconds
+pub fn now() -> u64 {
+    CLOCK.get_or_init(|| Box::new(SystemClock)).now()
+}
+
+/// A number picked at random below `bound`
+pub fn random_below(bound: u64) -> u64 {
+    let rng = RNG.get_or_init(|| Box::new(SeededRng::unseeded()));
+    rng.next_u64() % bound.max(1)
+}
@@ -0,0 +1,453 @@
+use crate::theme::Rgb;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// Everything read from `config.toml`; every section is optional
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The zellij binary to run, when it isn't just `zellij` from $PATH
+    pub zellij_bin: Option<PathBuf>,
+    /// Template used for new sessions when none is asked for explicitly
+    pub default_template: Option<String>,
+    pub matching: MatchingConfig,
+    pub ranking: RankingConfig,
+    pub theme: ThemeConfig,
+    /// Whether to draw with Unicode symbols or stick to ASCII
+    pub symbols: SymbolMode,
+    /// Writes numbers and dates like this locale (e.g. "de_DE") instead of
+    /// going by $LC_ALL, $LC_NUMERIC, $LC_TIME and $LANG
+    pub locale: Option<String>,
+    /// What Ctrl-C does at the picker's prompt
+    pub ctrl_c: CtrlC,
+    /// How the state (history, annotations, sets) is kept
+    pub state_backend: StateBackend,
+    /// Seconds a new session's server gets to come up before we give up on
+    /// it (5 unless set)
+    pub spawn_timeout: Option<u64>,
+    pub bell: BellConfig,
+    /// Title the terminal after the session attached to, until it's
+    /// detached from
+    pub terminal_title: bool,
+    pub idle: IdleConfig,
+    pub prompt: PromptConfig,
+    pub keys: KeysConfig,
+    /// Run for every session; a template's own hooks run after these
+    pub hooks: Hooks,
+    /// Variables whose current values are written out for the session on
+    /// every attach (like `SSH_AUTH_SOCK` after reconnecting over SSH),
+    /// for its shells to pick up from $ZELLIJ_CHOOSER_ENV_FILE
+    pub refresh_env: Vec<String>,
+    pub templates: BTreeMap<String, Template>,
+    /// Sessions brought up and down together, by group name
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Typed into the focused pane (with an Enter) right after attaching,
+    /// by session name; these win over a template's `run_on_attach`
+    pub run_on_attach: BTreeMap<String, String>,
+    /// Short names for sessions (`w = "work-main"`), taken on the command
+    /// line and in the picker wherever the session's own name is
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// The session `name` is an alias of, or `name` itself
+    pub fn unalias<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, String::as_str)
+    }
+}
+
+/// `base` picks a built-in palette, any color given here overrides it
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub base: ThemeBase,
+    /// The terminal's background, only used to check contrast
+    pub background: Option<Rgb>,
+    pub accent: Option<Rgb>,
+    pub attached: Option<Rgb>,
+    pub detached: Option<Rgb>,
+    pub dead: Option<Rgb>,
+}
+
+impl ThemeConfig {
+    pub fn is_customized(&self) -> bool {
+        self.background.is_some()
+            || self.accent.is_some()
+            || self.attached.is_some()
+            || self.detached.is_some()
+            || self.dead.is_some()
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeBase {
+    #[default]
+    Default,
+    /// Safe for protanopia, deuteranopia and tritanopia
+    Colorblind,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymbolMode {
+    /// Go by $TERM and the locale
+    #[default]
+    Auto,
+    Unicode,
+    Ascii,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CtrlC {
+    /// Leave the chooser (with exit status 130)
+    #[default]
+    Exit,
+    /// Throw away what's typed and start over, like a shell; Ctrl-D still
+    /// leaves
+    Clear,
+}
+
+/// How the picker asks
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PromptConfig {
+    /// What's written before the cursor (`>>> ` unless set); `{count}` is
+    /// replaced with how many sessions are listed, `{running}` and `{dead}`
+    /// with how many there are of each, and `{query}` with what the list is
+    /// narrowed down by
+    pub format: Option<String>,
+    /// Leave out the lines about what Enter and the other keys do
+    pub hide_help: bool,
+}
+
+/// When sessions count as idle, and how often the picker mentions them
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdleConfig {
+    /// Days without an attach (or since it started) before a session is idle
+    pub after_days: u64,
+    pub remind: Remind,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            after_days: 14,
+            remind: Remind::Daily,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Remind {
+    /// Every time the picker opens
+    Always,
+    #[default]
+    Daily,
+    Weekly,
+    Never,
+}
+
+/// How to get the user's attention once a slow action is done, for
+/// whoever switched to another window while waiting
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct BellConfig {
+    /// A session we started has come up
+    pub created: Alert,
+    /// Resurrectable sessions have been purged
+    pub purged: Alert,
+    /// A session started with `new --detached` has come up; rung instead
+    /// of `created` for it
+    pub detached: Alert,
+    /// Every session's socket has been probed, which is what a run waits
+    /// on before anything else when some server is slow to answer; only
+    /// when that took a couple of seconds or more
+    pub probes_done: Alert,
+    /// `prune` is done with the idle sessions
+    pub pruned: Alert,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Alert {
+    #[default]
+    Off,
+    /// The terminal bell
+    Bell,
+    /// Briefly flash the screen (reverse video)
+    Flash,
+    /// A desktop notification, asked of the terminal with an escape
+    /// sequence, so it works over SSH too
+    Notify,
+    /// A desktop notification sent with notify-send
+    Desktop,
+}
+
+/// A key in the picker, written `ctrl-x`, `alt-p`, `f2` or `pagedown` in the
+/// config, or just `s` for one that only acts on an empty line
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub enum Key {
+    Ctrl(char),
+    Alt(char),
+    F(u8),
+    PageUp,
+    PageDown,
+    Char(char),
+}
+
+impl TryFrom<String> for Key {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        let lower = raw.to_ascii_lowercase();
+        let single = |rest: &str| {
+            let mut chars = rest.chars();
+            chars.next().filter(|_| chars.next().is_none())
+        };
+        let key = if lower == "pageup" {
+            Some(Key::PageUp)
+        } else if lower == "pagedown" {
+            Some(Key::PageDown)
+        } else if let Some(rest) = lower.strip_prefix("ctrl-") {
+            single(rest)
+                .filter(char::is_ascii_alphabetic)
+                .map(Key::Ctrl)
+        } else if let Some(rest) = lower.strip_prefix("alt-") {
+            single(rest).filter(|c| !c.is_control()).map(Key::Alt)
+        } else if let Some(rest) = lower.strip_prefix('f').filter(|rest| !rest.is_empty()) {
+            rest.parse()
+                .ok()
+                .filter(|n| (1..=12).contains(n))
+                .map(Key::F)
+        } else {
+            single(&raw).filter(|c| c.is_ascii_graphic()).map(Key::Char)
+        };
+        key.ok_or_else(|| {
+            format!(
+                "\"{}\" isn't a key like ctrl-x, alt-p, f2, pagedown or s",
+                raw
+            )
+        })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Key::Ctrl(c) => write!(f, "Ctrl-{}", c.to_ascii_uppercase()),
+            Key::Alt(c) => write!(f, "Alt-{}", c.to_ascii_uppercase()),
+            Key::F(n) => write!(f, "F{}", n),
+            Key::PageUp => write!(f, "PageUp"),
+            Key::PageDown => write!(f, "PageDown"),
+            Key::Char(c) => write!(f, "{} (on an empty line)", c),
+        }
+    }
+}
+
+/// What each of the picker's keys does; Enter always attaches
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeysConfig {
+    /// Attach after detaching everyone else
+    pub force_attach: Key,
+    /// Create a session with exactly the typed name, even if it matches others
+    pub create: Key,
+    /// Kill a running session, or purge an exited one
+    pub kill: Key,
+    pub rename: Key,
+    /// Start a copy of a session, laid out the same
+    pub clone: Key,
+    /// Start tracking a session started elsewhere, tagging it
+    pub adopt: Key,
+    /// Show what's known about a session
+    pub details: Key,
+    /// Show a session's tabs
+    pub preview: Key,
+    /// Change how the list is sorted and grouped
+    pub sort: Key,
+    /// Page through a list too long for the terminal
+    pub next_page: Key,
+    pub prev_page: Key,
+}
+
+impl Default for KeysConfig {
+    fn default() -> Self {
+        Self {
+            force_attach: Key::Ctrl('f'),
+            create: Key::Ctrl('n'),
+            kill: Key::Ctrl('x'),
+            rename: Key::Ctrl('r'),
+            clone: Key::Alt('c'),
+            adopt: Key::Alt('a'),
+            details: Key::Ctrl('d'),
+            preview: Key::Alt('p'),
+            sort: Key::Char('s'),
+            next_page: Key::PageDown,
+            prev_page: Key::PageUp,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum StateBackend {
+    /// `state.json`, rewritten whole on every save
+    #[default]
+    File,
+    /// `state.sqlite`, for long histories; needs the `sqlite` feature
+    Sqlite,
+}
+
+/// A recipe for new sessions, picked with `new --template <name>`
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Template {
+    /// The name it goes by in the config, once it's been looked up
+    #[serde(skip)]
+    pub name: Option<String>,
+    /// Layout the session starts with (a name or a path, as zellij takes it)
+    pub layout: Option<String>,
+    /// Directory the session starts in, where `~/` is the home directory;
+    /// otherwise wherever the chooser was run
+    pub cwd: Option<PathBuf>,
+    /// Caps on the session's memory (`4G`), CPU (`200%` for two cores) and
+    /// process count, enforced through `systemd-run --user --scope`
+    pub memory_max: Option<String>,
+    pub cpu_quota: Option<String>,
+    pub tasks_max: Option<u32>,
+    /// Run the server, and so everything in the session, at a lower CPU
+    /// priority (0 to 19)
+    pub nice: Option<u8>,
+    pub ionice: Option<IoClass>,
+    /// Scheduling policy, applied with chrt
+    pub sched: Option<SchedPolicy>,
+    /// Set for the server, and so for everything started in the session
+    pub env: BTreeMap<String, String>,
+    /// Kept from the server instead; a trailing `*` takes in every name
+    /// starting with what comes before it (`ZELLIJ*`)
+    pub unset_env: Vec<String>,
+    /// Refreshed on attach on top of the global `refresh_env`
+    pub refresh_env: Vec<String>,
+    /// Typed into the focused pane (with an Enter) right after attaching
+    /// to one of its sessions, e.g. `git fetch`
+    pub run_on_attach: Option<String>,
+    /// Ask which tab to attach to whenever one of its sessions is chosen,
+    /// as with --pick-tab
+    pub pick_tab: bool,
+    pub hooks: Hooks,
+}
+
+/// Shell commands run (with `sh -c`) as sessions come and go. They get the
+/// session's name in $ZELLIJ_CHOOSER_SESSION and the event in
+/// $ZELLIJ_CHOOSER_EVENT.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    /// Once a new session's server is up
+    pub on_create: Option<String>,
+    /// Just before attaching
+    pub on_attach: Option<String>,
+    /// Once zellij exits after attaching, be it by detaching or otherwise
+    pub on_detach: Option<String>,
+}
+
+/// I/O scheduling classes, as ionice names them
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoClass {
+    Idle,
+    BestEffort,
+    Realtime,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SchedPolicy {
+    /// For CPU-bound batch jobs that shouldn't preempt interactive work
+    Batch,
+    /// Only runs when nothing else wants the CPU
+    Idle,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchingConfig {
+    pub case: CaseMode,
+    /// Treat `é` and `e` (and friends) as the same letter
+    pub ignore_diacritics: bool,
+}
+
+impl Default for MatchingConfig {
+    fn default() -> Self {
+        Self {
+            case: CaseMode::Smart,
+            ignore_diacritics: true,
+        }
+    }
+}
+
+/// How much a match on each field counts towards a session's rank
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RankingConfig {
+    pub name: f64,
+    pub tags: f64,
+    pub notes: f64,
+    pub cwd: f64,
+    pub frecency: f64,
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        Self {
+            name: 1.0,
+            tags: 0.8,
+            notes: 0.4,
+            cwd: 0.5,
+            frecency: 1.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaseMode {
+    /// Case-insensitive unless the query contains an uppercase letter
+    Smart,
+    Sensitive,
+    Insensitive,
+}
+
+/// `$XDG_CONFIG_HOME/zellij-chooser/config.toml`
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("zellij-chooser").join("config.toml"))
+}
+
+/// Reads the config at `path` (or the default location). A missing file
+/// just means defaults, but a malformed one is reported.
+pub fn load(path: Option<&Path>) -> io::Result<Config> {
+    let explicit = path.is_some();
+    let path = match path.map(Path::to_path_buf).or_else(default_path) {
+        Some(path) => path,
+        None => return Ok(Config::default()),
+    };
+    match fs::read_to_string(&path) {
+        Ok(raw) => toml::from_str(&raw).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound && !explicit => Ok(Config::default()),
+        Err(err) => Err(err),
+    }
+}
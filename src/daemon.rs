@@ -0,0 +1,166 @@
+use crate::events;
+use crate::snapshot::{Entry, Snapshot, Status};
+use crate::state::state_dir;
+use crate::watch::Watcher;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+// A client gets this long to send its request and read the answer
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where the daemon listens unless told otherwise: the runtime directory,
+/// since the socket means nothing after a reboot
+pub fn default_socket() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .map(|dir| dir.join("zellij-chooser.sock"))
+        .or_else(|| state_dir().map(|dir| dir.join("daemon.sock")))
+}
+
+/// A request is one line of JSON, e.g. `{"query": "summary"}`, answered
+/// with one line of JSON
+#[derive(Deserialize, Debug)]
+#[serde(tag = "query", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Request {
+    /// Every session, as `list --json --include-dead` would print them
+    Sessions,
+    /// One session, by name
+    Session { name: String },
+    /// Counts, for a status bar
+    Summary,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    pub running: usize,
+    pub attached: usize,
+    pub exited: usize,
+    /// When the table was last refreshed, in Unix seconds
+    pub taken: u64,
+}
+
+impl Summary {
+    fn of(snapshot: &Snapshot) -> Self {
+        let count =
+            |wanted: fn(&Entry) -> bool| snapshot.sessions.iter().filter(|e| wanted(e)).count();
+        Summary {
+            running: count(|entry| entry.status == Status::Running),
+            attached: count(|entry| entry.attached),
+            exited: count(|entry| entry.status == Status::Exited),
+            taken: snapshot.taken,
+        }
+    }
+}
+
+fn answer(table: &Snapshot, line: &str) -> serde_json::Value {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return json!({ "error": format!("Bad request: {}", err) }),
+    };
+    let answer = match request {
+        Request::Sessions => serde_json::to_value(table),
+        Request::Session { name } => match table.sessions.iter().find(|e| e.name == name) {
+            Some(entry) => serde_json::to_value(entry),
+            None => return json!({ "error": format!("No session named {}", name) }),
+        },
+        Request::Summary => serde_json::to_value(Summary::of(table)),
+    };
+    answer.unwrap_or_else(|err| json!({ "error": err.to_string() }))
+}
+
+fn serve(table: &RwLock<Snapshot>, stream: UnixStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut writer = &stream;
+    let mut line = String::new();
+    // Several requests can share a connection, one per line
+    while reader.read_line(&mut line)? > 0 {
+        if !line.trim().is_empty() {
+            let answer = answer(&table.read().unwrap(), line.trim());
+            writeln!(writer, "{}", answer)?;
+        }
+        line.clear();
+    }
+    Ok(())
+}
+
+// A socket left by a daemon that's gone is taken over; one that still
+// answers isn't
+fn bind(socket: &Path) -> io::Result<UnixListener> {
+    if UnixStream::connect(socket).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("a daemon is already listening on {}", socket.display()),
+        ));
+    }
+    match fs::remove_file(socket) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => (),
+    }
+    if let Some(dir) = socket.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    UnixListener::bind(socket)
+}
+
+/// `daemon`: keeps a table of the sessions up to date, refreshed as soon
+/// as a session starts or goes away and every `interval` regardless (to
+/// catch attaches and detaches), and answers queries about it on `socket`
+/// until killed
+pub fn run(socket_dir: &Path, socket: &Path, interval: Duration) -> io::Result<()> {
+    let listener = bind(socket)?;
+    log::info!("Listening on {}", socket.display());
+    let table = Arc::new(RwLock::new(events::look(socket_dir)));
+    {
+        let table = Arc::clone(&table);
+        let socket_dir = socket_dir.to_path_buf();
+        thread::spawn(move || {
+            let mut watcher = Watcher::new(&socket_dir, interval);
+            loop {
+                watcher.wait(interval);
+                let now = events::look(&socket_dir);
+                *table.write().unwrap() = now;
+            }
+        });
+    }
+    for stream in listener.incoming() {
+        match stream {
+            // Each on its own thread, so a slow client holds up nobody else
+            Ok(stream) => {
+                let table = Arc::clone(&table);
+                thread::spawn(move || {
+                    if let Err(err) = serve(&table, stream) {
+                        log::debug!("Dropped a client: {}", err);
+                    }
+                });
+            }
+            Err(err) => log::warn!("Couldn't accept a client: {}", err),
+        }
+    }
+    Ok(())
+}
+
+/// `daemon --query`: asks a running daemon, for scripts without a tool
+/// that speaks to Unix sockets. A bare word is taken as the query's name.
+pub fn query(socket: &Path, request: &str) -> io::Result<String> {
+    let request = if request.trim_start().starts_with('{') {
+        request.to_string()
+    } else {
+        json!({ "query": request }).to_string()
+    };
+    let stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut writer = &stream;
+    writeln!(writer, "{}", request)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut answer = String::new();
+    BufReader::new(&stream).read_line(&mut answer)?;
+    Ok(answer.trim_end().to_string())
+}
@@ -0,0 +1,219 @@
+use crate::clock;
+use crate::config::{Config, Hooks, Template};
+use crate::failure::Failure;
+use crate::listing::GroupBy;
+use crate::matcher::Matcher;
+use crate::picker::{interactive_select, Outcome, PickerOptions};
+use crate::ranking::Ranker;
+use crate::sessions;
+use crate::state::State;
+use crate::theme::Theme;
+use crate::zellij::Zellij;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// What the sandbox sessions (and anything created during the demo) look like
+const DEMO_LAYOUT: &str = r#"layout {
+    tab name="editor" focus=true {
+        pane
+    }
+    tab name="logs" {
+        pane split_direction="vertical" {
+            pane
+            pane
+        }
+    }
+}
+"#;
+
+const SANDBOX_SESSIONS: [&str; 2] = ["demo-editor", "demo-logs"];
+
+// What the user is asked to do, and what the picker should come back with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Select,
+    Preview,
+    Create,
+    Kill,
+}
+
+impl Step {
+    const ALL: [Step; 4] = [Step::Select, Step::Preview, Step::Create, Step::Kill];
+
+    fn instructions(self, config: &Config) -> String {
+        match self {
+            Step::Select => "Selecting: type part of a session's name (say \"log\") and press \
+                 Enter. A unique match is picked straight away; several narrow the list down."
+                .to_string(),
+            Step::Preview => format!(
+                "Previewing: type \"ed\", then press {} instead of Enter to see its tabs \
+                 without attaching. Press Enter on a session when you've seen enough.",
+                config.keys.preview
+            ),
+            Step::Create => format!(
+                "Creating: type a name that isn't in the list and press {}. The demo \
+                 starts it from its template, like `new --template` would.",
+                config.keys.create
+            ),
+            Step::Kill => format!(
+                "Killing: type a session's name and press {}, then confirm with y.",
+                config.keys.kill
+            ),
+        }
+    }
+}
+
+/// Walks through the picker with a couple of throwaway sessions, in a
+/// socket directory of their own so the user's sessions and state stay out
+/// of it. Everything it started is cleaned up however it ends.
+pub fn run(zellij: &Zellij, config: &Config, theme: Option<Theme>) -> Result<(), Failure> {
+    let sandbox = env::temp_dir().join(format!("zellij-chooser-demo-{}", std::process::id()));
+    let zellij = Zellij::new(
+        zellij.bin.clone(),
+        zellij.extra_args.clone(),
+        Some(sandbox.clone()),
+    );
+    // The sandbox sessions aren't anyone's business
+    let config = Config {
+        hooks: Hooks::default(),
+        ..config.clone()
+    };
+    let result = walk_through(&zellij, &config, theme, &sandbox);
+    clean_up(&zellij, &sandbox);
+    result
+}
+
+fn walk_through(
+    zellij: &Zellij,
+    config: &Config,
+    theme: Option<Theme>,
+    sandbox: &Path,
+) -> Result<(), Failure> {
+    let socket_dir = sessions::socket_dir(Some(sandbox));
+    fs::create_dir_all(&socket_dir)
+        .map_err(|err| Failure::Other(format!("Couldn't set up the demo: {}", err)))?;
+    let layout = sandbox.join("demo.kdl");
+    fs::write(&layout, DEMO_LAYOUT)
+        .map_err(|err| Failure::Other(format!("Couldn't set up the demo: {}", err)))?;
+    let template = Template {
+        layout: Some(layout.to_string_lossy().into_owned()),
+        ..Template::default()
+    };
+    println!("Welcome! This demo runs the picker against a few sandbox sessions, which are");
+    println!("all gone again once it's over (or Ctrl-C'd). Nothing ever gets attached to.\n");
+    for session in SANDBOX_SESSIONS {
+        spawn(zellij, &socket_dir, config, session, &template)?;
+    }
+
+    let matcher = Matcher::new(&config.matching);
+    let state = State::default();
+    for (number, step) in Step::ALL.into_iter().enumerate() {
+        println!(
+            "\nStep {}/{}. {}",
+            number + 1,
+            Step::ALL.len(),
+            step.instructions(config)
+        );
+        loop {
+            let running = sessions::get_sessions(&socket_dir).unwrap_or_default();
+            let ranker = Ranker::new(&matcher, &config.ranking, &state, clock::now());
+            let options = PickerOptions {
+                show_banner: false,
+                hide_help: false,
+                prompt: None,
+                summary: None,
+                theme,
+                explain_ranking: false,
+                initial_query: None,
+                accept_single_match: false,
+                history_file: None,
+                history: Vec::new(),
+                auto_preview: false,
+                keys: config.keys.clone(),
+                ctrl_c: config.ctrl_c,
+                attached: Vec::new(),
+                last_used: HashMap::new(),
+                groups: BTreeMap::new(),
+                aliases: BTreeMap::new(),
+                sort: None,
+                group_by: GroupBy::None,
+                group_labels: HashMap::new(),
+                watch: None,
+            };
+            let preview = |session: &str| {
+                let layout = zellij.dump_layout(session).ok()?;
+                Some(crate::preview::render(
+                    session,
+                    &crate::preview::summarize(&layout),
+                ))
+            };
+            let details = |_: &str| -> Option<String> { None };
+            let outcome = interactive_select(&running, &[], &ranker, options, &preview, &details)?;
+            let done = match (step, outcome) {
+                (Step::Select | Step::Preview, Outcome::Attach(selection))
+                    if running.contains(&selection.name) =>
+                {
+                    println!(
+                        "That's it: the chooser would attach to {} now.",
+                        selection.name
+                    );
+                    true
+                }
+                (Step::Create, Outcome::Attach(selection))
+                    if !running.contains(&selection.name) =>
+                {
+                    spawn(zellij, &socket_dir, config, &selection.name, &template)?;
+                    println!(
+                        "{} is up, it'll be in the list from now on.",
+                        selection.name
+                    );
+                    true
+                }
+                (Step::Kill, Outcome::Kill(name)) => {
+                    zellij.kill_session(&name).map_err(|err| {
+                        Failure::from_zellij(err, format!("Couldn't kill \"{}\"", name))
+                    })?;
+                    println!("{} is gone.", name);
+                    true
+                }
+                _ => {
+                    println!("Not quite, try again:");
+                    false
+                }
+            };
+            if done {
+                break;
+            }
+        }
+    }
+    println!("\nThat's the tour. Run zellij-chooser on its own to use it for real.");
+    Ok(())
+}
+
+fn spawn(
+    zellij: &Zellij,
+    socket_dir: &Path,
+    config: &Config,
+    session: &str,
+    template: &Template,
+) -> Result<(), Failure> {
+    crate::spawn(zellij, socket_dir, config, session, template).map_err(|err| {
+        Failure::from_zellij(
+            err,
+            format!("Couldn't start the demo session \"{}\"", session),
+        )
+    })
+}
+
+// Kills whatever is left in the sandbox and removes it
+fn clean_up(zellij: &Zellij, sandbox: &Path) {
+    let socket_dir = sessions::socket_dir(Some(sandbox));
+    for session in sessions::get_sessions(&socket_dir).unwrap_or_default() {
+        if let Err(err) = zellij.kill_session(&session) {
+            log::warn!("Couldn't kill the demo session {}: {}", session, err);
+        }
+    }
+    let _ = fs::remove_dir_all(sandbox);
+}
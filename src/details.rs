@@ -0,0 +1,99 @@
+use crate::locale;
+use crate::procfs::{self, Usage};
+use crate::state::SessionRecord;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// "5m ago" and the like, for `then` and `now` in Unix seconds
+pub fn ago(then: u64, now: u64) -> String {
+    let count = |n| locale::current().count(n);
+    match now.saturating_sub(then) {
+        secs if secs < 60 => "just now".to_string(),
+        secs if secs < 60 * 60 => format!("{}m ago", count(secs / 60)),
+        secs if secs < 24 * 60 * 60 => format!("{}h ago", count(secs / (60 * 60))),
+        secs => format!("{}d ago", count(secs / (24 * 60 * 60))),
+    }
+}
+
+/// "45s", "5m", "3h20m" or "2d4h", for a length of time in seconds
+pub fn duration(secs: u64) -> String {
+    let count = |n| locale::current().count(n);
+    match secs {
+        secs if secs < 60 => format!("{}s", secs),
+        secs if secs < 60 * 60 => format!("{}m", secs / 60),
+        secs if secs < 24 * 60 * 60 => format!("{}h{:02}m", secs / (60 * 60), secs / 60 % 60),
+        secs => format!(
+            "{}d{}h",
+            count(secs / (24 * 60 * 60)),
+            secs / (60 * 60) % 24
+        ),
+    }
+}
+
+// "5m ago (15.10.2026 14:03)", for when there's room for the exact time too
+fn ago_at(then: u64, now: u64) -> String {
+    format!("{} ({})", ago(then, now), locale::current().datetime(then))
+}
+
+/// Everything we know about a session, for the picker's details key
+pub fn render(
+    name: &str,
+    running: bool,
+    created: Option<SystemTime>,
+    record: Option<&SessionRecord>,
+    usage: Option<Usage>,
+    priority: Option<&str>,
+    now: u64,
+) -> String {
+    let locale = locale::current();
+    let mut out = String::new();
+    let status = if running { "running" } else { "exited" };
+    let _ = write!(out, "{} ({})", name, status);
+    let created = created
+        .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+        .map(|at| at.as_secs());
+    if let Some(created) = created {
+        let verb = if running { "started" } else { "saved" };
+        let _ = write!(out, "\n  {} {}", verb, ago_at(created, now));
+    }
+    if let Some(usage) = usage {
+        let _ = write!(
+            out,
+            "\n  using {} and {} of CPU over {} process(es)",
+            procfs::format_kib(usage.rss_kib),
+            procfs::format_ticks(usage.cpu_ticks),
+            locale.count(usage.processes as u64)
+        );
+    }
+    if let Some(priority) = priority {
+        let _ = write!(out, "\n  priority: {}", priority);
+    }
+    let Some(record) = record else {
+        return out;
+    };
+    if let Some(last) = record.attaches.last() {
+        let _ = write!(
+            out,
+            "\n  attached {} time(s), last {}",
+            locale.count(record.times_attached()),
+            ago_at(*last, now)
+        );
+        if record.attached_secs > 0 {
+            let _ = write!(
+                out,
+                " ({} attached all told)",
+                duration(record.attached_secs)
+            );
+        }
+    }
+    if !record.tags.is_empty() {
+        let _ = write!(out, "\n  tags: {}", record.tags.join(", "));
+    }
+    if !record.notes.is_empty() {
+        let _ = write!(out, "\n  note: {}", record.notes);
+    }
+    if let Some(cwd) = &record.cwd {
+        let _ = write!(out, "\n  cwd: {}", cwd.display());
+    }
+    out
+}
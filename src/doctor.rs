@@ -0,0 +1,246 @@
+use crate::dryrun;
+use crate::failure::Failure;
+use crate::sessions;
+use crate::zellij::{self, Zellij};
+use nix::unistd::getuid;
+use std::fs::{self, DirBuilder};
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+// What zellij makes its socket directory with: its sockets are nobody
+// else's business
+const SOCKET_DIR_MODE: u32 = 0o700;
+
+enum Fix {
+    CreateDir(PathBuf),
+    Chmod(PathBuf),
+    Remove(PathBuf),
+}
+
+impl Fix {
+    fn describe(&self) -> String {
+        match self {
+            Fix::CreateDir(dir) => format!("create {}", dir.display()),
+            Fix::Chmod(dir) => format!(
+                "make {} only yours (mode {:o})",
+                dir.display(),
+                SOCKET_DIR_MODE
+            ),
+            Fix::Remove(path) => format!("remove {}", path.display()),
+        }
+    }
+
+    fn apply(&self) -> io::Result<()> {
+        match self {
+            Fix::CreateDir(dir) => DirBuilder::new()
+                .recursive(true)
+                .mode(SOCKET_DIR_MODE)
+                .create(dir),
+            Fix::Chmod(dir) => {
+                fs::set_permissions(dir, fs::Permissions::from_mode(SOCKET_DIR_MODE))
+            }
+            Fix::Remove(path) => fs::remove_file(path),
+        }
+    }
+}
+
+// What's wrong, and what can be done about it, if anything
+struct Problem {
+    what: String,
+    fix: Option<Fix>,
+}
+
+#[derive(Default)]
+struct Checkup {
+    problems: Vec<Problem>,
+}
+
+impl Checkup {
+    fn ok(&self, what: String) {
+        println!("ok       {}", what);
+    }
+
+    fn problem(&mut self, what: String, fix: Option<Fix>) {
+        println!("problem  {}", what);
+        self.problems.push(Problem { what, fix });
+    }
+}
+
+fn check_zellij(checkup: &mut Checkup, zellij: &Zellij) {
+    match zellij.version_number() {
+        Err(err) => checkup.problem(
+            format!(
+                "zellij isn't available (tried `{}`): {}",
+                zellij.bin.display(),
+                err
+            ),
+            None,
+        ),
+        Ok(version) if version != zellij::PROTOCOL_VERSION => checkup.problem(
+            format!(
+                "{} is zellij {}, but the chooser speaks the zellij {} protocol, so its \
+                 sessions won't show up",
+                zellij.bin.display(),
+                version,
+                zellij::PROTOCOL_VERSION
+            ),
+            None,
+        ),
+        Ok(version) => checkup.ok(format!("{} is zellij {}", zellij.bin.display(), version)),
+    }
+}
+
+// Whether there's a directory to look in after this
+fn check_socket_dir(checkup: &mut Checkup, socket_dir: &Path) -> bool {
+    let shown = socket_dir.display();
+    let metadata = match fs::metadata(socket_dir) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            checkup.problem(
+                format!("the socket directory {} doesn't exist", shown),
+                Some(Fix::CreateDir(socket_dir.to_path_buf())),
+            );
+            return false;
+        }
+        Err(err) => {
+            checkup.problem(format!("can't look at {}: {}", shown, err), None);
+            return false;
+        }
+    };
+    if !metadata.is_dir() {
+        checkup.problem(
+            format!("{} isn't a directory; move it out of zellij's way", shown),
+            None,
+        );
+        return false;
+    }
+    if metadata.uid() != getuid().as_raw() {
+        checkup.problem(
+            format!(
+                "{} belongs to user {}, not you (--socket-dir can point elsewhere)",
+                shown,
+                metadata.uid()
+            ),
+            None,
+        );
+        return false;
+    }
+    let mode = metadata.mode() & 0o777;
+    if mode & 0o700 != 0o700 || mode & 0o077 != 0 {
+        checkup.problem(
+            format!("{} has mode {:o}", shown, mode),
+            Some(Fix::Chmod(socket_dir.to_path_buf())),
+        );
+    } else {
+        checkup.ok(format!("the socket directory is {}", shown));
+    }
+    true
+}
+
+fn check_sockets(checkup: &mut Checkup, socket_dir: &Path) {
+    let entries = match fs::read_dir(socket_dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            checkup.problem(
+                format!("can't list {}: {}", socket_dir.display(), err),
+                None,
+            );
+            return;
+        }
+    };
+    let mut sessions = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() {
+            checkup.problem(
+                format!(
+                    "{} is a directory among the sockets; left alone",
+                    path.display()
+                ),
+                None,
+            );
+            continue;
+        }
+        if !kind.is_socket() {
+            checkup.problem(
+                format!("{} isn't a socket", path.display()),
+                Some(Fix::Remove(path)),
+            );
+            continue;
+        }
+        // Before probing, which would clean it up by itself
+        match UnixStream::connect(&path) {
+            Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                checkup.problem(
+                    format!("{}'s server is gone, but its socket isn't", name),
+                    Some(Fix::Remove(path)),
+                );
+                continue;
+            }
+            _ => (),
+        }
+        let trouble = sessions::probe(socket_dir, &name).and_then(|status| status.trouble());
+        match trouble {
+            Some(trouble) => checkup.problem(
+                format!("{} is {} (`kill` may get rid of it)", name, trouble),
+                None,
+            ),
+            None => sessions += 1,
+        }
+    }
+    checkup.ok(format!("{} session(s) answering", sessions));
+}
+
+/// `doctor`: looks over zellij and the socket directory, and offers to put
+/// right what it can, asking first unless `yes`. Without a terminal to ask
+/// on, and without `yes`, it only reports.
+pub fn run(zellij: &Zellij, socket_dir: &Path, yes: bool) -> Result<(), Failure> {
+    let mut checkup = Checkup::default();
+    check_zellij(&mut checkup, zellij);
+    if check_socket_dir(&mut checkup, socket_dir) {
+        check_sockets(&mut checkup, socket_dir);
+    }
+    let can_ask = io::stdin().is_terminal();
+    let mut left = 0;
+    for problem in checkup.problems {
+        let Some(fix) = problem.fix.filter(|_| yes || can_ask) else {
+            left += 1;
+            continue;
+        };
+        if !yes {
+            print!("{}: {}? [y/N] ", problem.what, fix.describe());
+            let _ = io::stdout().flush();
+            let mut answer = String::new();
+            if io::stdin()
+                .read_line(&mut answer)
+                .map_err(|err| err.to_string())?
+                == 0
+            {
+                return Err(Failure::Aborted);
+            }
+            if !answer.trim().eq_ignore_ascii_case("y") {
+                left += 1;
+                continue;
+            }
+        }
+        if dryrun::pretend(fix.describe()) {
+            left += 1;
+            continue;
+        }
+        println!("fixing   {}", fix.describe());
+        if let Err(err) = fix.apply() {
+            eprintln!("Couldn't {}: {}", fix.describe(), err);
+            left += 1;
+        }
+    }
+    if left > 0 {
+        return Err(Failure::Other(format!("{} problem(s) left", left)));
+    }
+    Ok(())
+}
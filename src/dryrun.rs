@@ -0,0 +1,23 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// From now on, nothing is started, attached to, killed, written or
+/// removed: `pretend` says what would have been instead
+pub fn enable() {
+    DRY_RUN.store(true, Ordering::Relaxed);
+}
+
+pub fn is_on() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// For just before doing `what`: with --dry-run, prints that it would be
+/// done and returns true, for the caller to leave it at that
+pub fn pretend(what: impl Display) -> bool {
+    if is_on() {
+        println!("Would {}", what);
+    }
+    is_on()
+}
@@ -0,0 +1,239 @@
+use crate::listing::Filter;
+use crate::locale;
+use crate::sessions;
+use crate::snapshot::{self, Snapshot, Status};
+use crate::watch::Watcher;
+use futures_core::Stream;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+/// Something that happened to a session
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// Started, or resurrected
+    Created {
+        session: String,
+    },
+    Renamed {
+        from: String,
+        to: String,
+    },
+    /// Killed, or exited and left resurrectable
+    Died {
+        session: String,
+        resurrectable: bool,
+    },
+    /// A first client attached
+    Attached {
+        session: String,
+    },
+    /// The last client detached
+    Detached {
+        session: String,
+    },
+}
+
+impl Event {
+    fn session(&self) -> &str {
+        match self {
+            Event::Renamed { to, .. } => to,
+            Event::Created { session }
+            | Event::Died { session, .. }
+            | Event::Attached { session }
+            | Event::Detached { session } => session,
+        }
+    }
+}
+
+/// What happened between two looks at the sessions, in the order `diff`
+/// finds it, followed by attaches and detaches
+pub fn changes(before: &Snapshot, now: &Snapshot) -> Vec<Event> {
+    let diff = snapshot::diff(before, now);
+    let mut events: Vec<Event> = diff
+        .created
+        .into_iter()
+        .chain(diff.revived)
+        .map(|session| Event::Created { session })
+        .collect();
+    events.extend(
+        diff.renamed
+            .into_iter()
+            .map(|(from, to)| Event::Renamed { from, to }),
+    );
+    events.extend(diff.killed.into_iter().map(|session| Event::Died {
+        session,
+        resurrectable: false,
+    }));
+    events.extend(diff.exited.into_iter().map(|session| Event::Died {
+        session,
+        resurrectable: true,
+    }));
+    for entry in &now.sessions {
+        let Some(old) = before.sessions.iter().find(|old| old.name == entry.name) else {
+            continue;
+        };
+        if old.status != Status::Running || entry.status != Status::Running {
+            continue;
+        }
+        match (old.attached, entry.attached) {
+            (false, true) => events.push(Event::Attached {
+                session: entry.name.clone(),
+            }),
+            (true, false) => events.push(Event::Detached {
+                session: entry.name.clone(),
+            }),
+            _ => (),
+        }
+    }
+    events
+}
+
+/// The sessions' lifecycle as a stream of events; `next` blocks until
+/// there's something to report. There's nothing to subscribe to in zellij
+/// itself, so a `Watcher` on the socket directory wakes it as soon as a
+/// session starts or goes away, and it looks every `interval` regardless,
+/// which is when attaches and detaches (which leave the socket directory
+/// alone) are noticed.
+///
+/// Exits are only told apart from kills once `sessions::init_resurrection`
+/// has said where to find the sessions zellij can resurrect.
+pub struct SessionEvents {
+    socket_dir: PathBuf,
+    watcher: Watcher,
+    interval: Duration,
+    last: Snapshot,
+    pending: VecDeque<Event>,
+}
+
+impl SessionEvents {
+    pub fn new(socket_dir: &Path, interval: Duration) -> Self {
+        SessionEvents {
+            socket_dir: socket_dir.to_path_buf(),
+            watcher: Watcher::new(socket_dir, interval),
+            interval,
+            last: look(socket_dir),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The same events as an async `Stream`. The waiting is done on a
+    /// thread of its own, which wakes the stream's task whenever there's
+    /// an event, and goes away with the first event after the stream does.
+    pub fn into_stream(self) -> EventStream {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let feeding = Arc::downgrade(&shared);
+        thread::spawn(move || {
+            for event in self {
+                let Some(shared) = feeding.upgrade() else {
+                    return;
+                };
+                let mut shared = shared.lock().unwrap();
+                shared.pending.push_back(event);
+                if let Some(waker) = shared.waker.take() {
+                    waker.wake();
+                }
+            }
+        });
+        EventStream { shared }
+    }
+}
+
+impl Iterator for SessionEvents {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            self.watcher.wait(self.interval);
+            let now = look(&self.socket_dir);
+            self.pending.extend(changes(&self.last, &now));
+            self.last = now;
+        }
+    }
+}
+
+// What the thread behind an `EventStream` hands over
+#[derive(Default)]
+struct Shared {
+    pending: VecDeque<Event>,
+    waker: Option<Waker>,
+}
+
+/// `SessionEvents` for async code, see `SessionEvents::into_stream`
+pub struct EventStream {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Stream for EventStream {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.pending.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// Exited sessions are looked at too, to tell exits from kills
+pub fn look(socket_dir: &Path) -> Snapshot {
+    let running = sessions::get_sessions(socket_dir).unwrap_or_default();
+    let dead = sessions::get_dead_sessions(&running);
+    Snapshot::take(socket_dir, &running, Some(&dead))
+}
+
+/// `events`: prints events as they happen, until interrupted
+pub fn run(
+    socket_dir: &Path,
+    filter: Option<&Filter>,
+    interval: Duration,
+    json: bool,
+) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    for event in SessionEvents::new(socket_dir, interval) {
+        if filter.is_some_and(|filter| !filter.matches(event.session())) {
+            continue;
+        }
+        if json {
+            let line = serde_json::to_string(&event).map_err(io::Error::other)?;
+            writeln!(stdout, "{}", line)?;
+        } else {
+            writeln!(
+                stdout,
+                "{} {}",
+                locale::current().datetime(crate::clock::now()),
+                describe(&event)
+            )?;
+        }
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+fn describe(event: &Event) -> String {
+    match event {
+        Event::Created { session } => format!("+ {} created", session),
+        Event::Renamed { from, to } => format!("~ {} renamed to {}", from, to),
+        Event::Died {
+            session,
+            resurrectable: true,
+        } => format!("x {} exited (resurrectable)", session),
+        Event::Died { session, .. } => format!("- {} killed", session),
+        Event::Attached { session } => format!("> {} attached", session),
+        Event::Detached { session } => format!("< {} detached", session),
+    }
+}
@@ -0,0 +1,86 @@
+use rustyline::error::ReadlineError;
+use std::fmt;
+use std::io;
+use std::process;
+
+/// Why the chooser gave up. Each kind exits with its own status so scripts
+/// can tell them apart; the README lists them under "Exit codes".
+#[derive(Debug)]
+pub enum Failure {
+    /// Anything not covered below (1)
+    Other(String),
+    /// The zellij binary is missing, broken, or the wrong version (3)
+    Zellij(String),
+    /// A session, template or tab that was asked for doesn't exist (4)
+    NotFound(String),
+    /// A session's server didn't answer, or not the way it should (5)
+    Ipc(String),
+    /// The picker was needed, but there's no terminal to show it on (6)
+    NoTerminal(String),
+    /// The user backed out, e.g. with Ctrl-C or Ctrl-D at the prompt (130,
+    /// as if SIGINT had killed us)
+    Aborted,
+}
+
+impl Failure {
+    pub fn code(&self) -> i32 {
+        match self {
+            Failure::Other(_) => 1,
+            Failure::Zellij(_) => 3,
+            Failure::NotFound(_) => 4,
+            Failure::Ipc(_) => 5,
+            Failure::NoTerminal(_) => 6,
+            Failure::Aborted => 130,
+        }
+    }
+
+    /// Sorts an error from running a zellij command: not finding the binary,
+    /// or it being too old for what was asked, is zellij's fault, a bad
+    /// session name is nobody's in particular, anything else is the session's
+    pub fn from_zellij(err: io::Error, context: String) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound | io::ErrorKind::Unsupported => {
+                Failure::Zellij(format!("{}: {}", context, err))
+            }
+            io::ErrorKind::InvalidInput => Failure::Other(format!("{}: {}", context, err)),
+            _ => Failure::Ipc(format!("{}: {}", context, err)),
+        }
+    }
+
+    pub fn exit(self) -> ! {
+        if !matches!(self, Failure::Aborted) {
+            eprintln!("{}", self);
+        }
+        process::exit(self.code())
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Other(msg)
+            | Failure::Zellij(msg)
+            | Failure::NotFound(msg)
+            | Failure::Ipc(msg)
+            | Failure::NoTerminal(msg) => write!(f, "{}", msg),
+            Failure::Aborted => write!(f, "Aborted"),
+        }
+    }
+}
+
+// What the picker's prompt failing means: the user leaving it, or a
+// broken terminal
+impl From<ReadlineError> for Failure {
+    fn from(err: ReadlineError) -> Self {
+        match err {
+            ReadlineError::Interrupted | ReadlineError::Eof => Failure::Aborted,
+            err => Failure::Other(format!("The picker failed: {}", err)),
+        }
+    }
+}
+
+impl From<String> for Failure {
+    fn from(msg: String) -> Self {
+        Failure::Other(msg)
+    }
+}
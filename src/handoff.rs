@@ -0,0 +1,143 @@
+use crate::signals;
+use std::io;
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How far the handoff had got when it was cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// A session was chosen, nothing was forked yet
+    Selected,
+    /// In the daemonized fork, before the client was started
+    Forked,
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    /// Stopped before a client was started, so there's nothing to clean up
+    Cancelled(Phase),
+    /// This process passed the terminal on to its fork and is done
+    HandedOff,
+    /// The client ran, and this is how it exited
+    Finished(ExitStatus),
+}
+
+/// Hands the terminal from us to a zellij client, in steps: `fork`
+/// daemonizes (returning whether this is the process that carries on), then
+/// `spawn` starts the client. A signal `pending` before either step cancels
+/// the rest, and one arriving while the client starts is passed on to it
+/// the moment it's running, so a Ctrl-C at any point leaves no fork or
+/// client behind.
+pub fn attach(
+    pending: &AtomicUsize,
+    fork: impl FnOnce() -> io::Result<bool>,
+    spawn: impl FnOnce() -> io::Result<Child>,
+) -> io::Result<Outcome> {
+    let cancelled = || pending.load(Ordering::SeqCst) != 0;
+    if cancelled() {
+        return Ok(Outcome::Cancelled(Phase::Selected));
+    }
+    if !fork()? {
+        return Ok(Outcome::HandedOff);
+    }
+    if cancelled() {
+        return Ok(Outcome::Cancelled(Phase::Forked));
+    }
+    let mut child = spawn()?;
+    log::info!("zellij attach is pid {}", child.id());
+    // Stick around until it's done, so that anything told to stop us
+    // reaches zellij too
+    signals::wait_forwarding(&mut child, pending).map(Outcome::Finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use signal_hook::consts::{SIGINT, SIGTERM};
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    fn client() -> io::Result<Child> {
+        Command::new("sleep").arg("30").spawn()
+    }
+
+    #[test]
+    fn cancelled_once_selected_forks_nothing() {
+        let pending = AtomicUsize::new(SIGINT as usize);
+        let outcome = attach(
+            &pending,
+            || panic!("forked after a cancel"),
+            || panic!("started a client after a cancel"),
+        )
+        .unwrap();
+        assert!(matches!(outcome, Outcome::Cancelled(Phase::Selected)));
+    }
+
+    #[test]
+    fn cancelled_while_forking_starts_no_client() {
+        let pending = AtomicUsize::new(0);
+        let outcome = attach(
+            &pending,
+            || {
+                pending.store(SIGINT as usize, Ordering::SeqCst);
+                Ok(true)
+            },
+            || panic!("started a client after a cancel"),
+        )
+        .unwrap();
+        assert!(matches!(outcome, Outcome::Cancelled(Phase::Forked)));
+    }
+
+    #[test]
+    fn cancelled_while_the_client_starts_stops_it() {
+        let pending = AtomicUsize::new(0);
+        let started = Instant::now();
+        let outcome = attach(
+            &pending,
+            || Ok(true),
+            || {
+                let child = client();
+                pending.store(SIGTERM as usize, Ordering::SeqCst);
+                child
+            },
+        )
+        .unwrap();
+        let Outcome::Finished(status) = outcome else {
+            panic!("expected the client to have run, got {:?}", outcome);
+        };
+        assert_eq!(status.signal(), Some(SIGTERM));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(pending.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn the_parent_is_done_once_it_has_forked() {
+        let pending = AtomicUsize::new(0);
+        let outcome = attach(
+            &pending,
+            || Ok(false),
+            || panic!("the parent started a client"),
+        )
+        .unwrap();
+        assert!(matches!(outcome, Outcome::HandedOff));
+    }
+
+    #[test]
+    fn uncancelled_runs_the_client_to_the_end() {
+        let pending = AtomicUsize::new(0);
+        let outcome = attach(&pending, || Ok(true), || Command::new("true").spawn()).unwrap();
+        assert!(matches!(outcome, Outcome::Finished(status) if status.success()));
+    }
+
+    #[test]
+    fn a_client_that_cannot_start_is_an_error() {
+        let pending = AtomicUsize::new(0);
+        let result = attach(
+            &pending,
+            || Ok(true),
+            || Command::new("/nonexistent/zellij").spawn(),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use crate::config::{Config, Hooks, Template};
+use crate::dryrun;
+use crate::launcher;
+use crate::state::State;
+use std::process::{Command, Stdio};
+
+/// Where in a session's life a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Create,
+    Attach,
+    Detach,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Create => "create",
+            Event::Attach => "attach",
+            Event::Detach => "detach",
+        }
+    }
+
+    fn command(self, hooks: &Hooks) -> Option<&str> {
+        match self {
+            Event::Create => hooks.on_create.as_deref(),
+            Event::Attach => hooks.on_attach.as_deref(),
+            Event::Detach => hooks.on_detach.as_deref(),
+        }
+    }
+}
+
+/// The hooks for a session started from `template`: the global ones, then
+/// the template's
+pub fn applying<'c>(config: &'c Config, template: Option<&'c Template>) -> Vec<&'c Hooks> {
+    let mut hooks = vec![&config.hooks];
+    hooks.extend(template.map(|template| &template.hooks));
+    hooks
+}
+
+/// The hooks for a session that's already around, going by the template
+/// it was started from, if we were the ones to start it
+pub fn for_session<'c>(config: &'c Config, state: &State, session: &str) -> Vec<&'c Hooks> {
+    applying(config, state.template_of(config, session))
+}
+
+/// Runs the `event` hooks one after the other, with the terminal but not
+/// stdin. One failing is reported and doesn't stop the rest, or whatever
+/// the hook was for.
+pub fn run(hooks: &[&Hooks], event: Event, session: &str) {
+    let env_file = launcher::env_file(session).filter(|file| file.is_file());
+    for command in hooks.iter().filter_map(|hooks| event.command(hooks)) {
+        if dryrun::pretend(format_args!(
+            "run the on_{} hook: {}",
+            event.name(),
+            command
+        )) {
+            continue;
+        }
+        log::info!("Running the on_{} hook: {}", event.name(), command);
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(command)
+            .env("ZELLIJ_CHOOSER_SESSION", session)
+            .env("ZELLIJ_CHOOSER_EVENT", event.name())
+            .stdin(Stdio::null());
+        if let Some(file) = &env_file {
+            cmd.env("ZELLIJ_CHOOSER_ENV_FILE", file);
+        }
+        let status = cmd.status();
+        match status {
+            Ok(status) if status.success() => (),
+            Ok(status) => eprintln!("The on_{} hook exited with {}", event.name(), status),
+            Err(err) => eprintln!("Couldn't run the on_{} hook: {}", event.name(), err),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::config::{IdleConfig, Remind};
+use crate::locale;
+use crate::sessions;
+use crate::state::State;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// When a session was last of any use: its last attach, or when it started
+/// if that's later (a fresh session nobody attached to yet isn't idle)
+pub fn last_used(socket_dir: &Path, session: &str, state: &State) -> Option<u64> {
+    let attached = state
+        .sessions
+        .get(session)
+        .and_then(|record| record.attaches.last().copied());
+    let created = sessions::created_at(socket_dir, session)
+        .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+        .map(|at| at.as_secs());
+    attached.max(created)
+}
+
+/// The sessions unused for `days` or more, longest idle first, with when
+/// they were last used. Anyone attached right now counts as use.
+pub fn idle_sessions(
+    sessions: &[String],
+    socket_dir: &Path,
+    state: &State,
+    days: u64,
+    now: u64,
+) -> Vec<(String, u64)> {
+    let mut idle: Vec<(String, u64)> = sessions
+        .iter()
+        .filter_map(|session| Some((session.clone(), last_used(socket_dir, session, state)?)))
+        .filter(|(_, used)| now.saturating_sub(*used) >= days * DAY)
+        .filter(|(session, _)| !sessions::is_attached(socket_dir, session))
+        .collect();
+    idle.sort_by_key(|(_, used)| *used);
+    idle
+}
+
+/// The banner nudging towards `prune`, when there's something idle and it
+/// hasn't been shown too recently. Marks it shown in `state`.
+pub fn reminder(
+    config: &IdleConfig,
+    sessions: &[String],
+    socket_dir: &Path,
+    state: &mut State,
+    now: u64,
+) -> Option<String> {
+    let every = match config.remind {
+        Remind::Never => return None,
+        Remind::Always => 0,
+        Remind::Daily => DAY,
+        Remind::Weekly => 7 * DAY,
+    };
+    let due = state
+        .idle_reminded
+        .is_none_or(|at| now.saturating_sub(at) >= every);
+    if !due {
+        return None;
+    }
+    let idle = idle_sessions(sessions, socket_dir, state, config.after_days, now);
+    if idle.is_empty() {
+        return None;
+    }
+    state.idle_reminded = Some(now);
+    Some(format!(
+        "{} session(s) idle >{} days — run `zellij-chooser prune --interactive`?",
+        locale::current().count(idle.len() as u64),
+        locale::current().count(config.after_days)
+    ))
+}
@@ -0,0 +1,159 @@
+use crate::config::{Config, IoClass, SchedPolicy, Template};
+use crate::dryrun;
+use crate::sessions;
+use crate::state::runtime_dir;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs, io};
+
+/// What to run zellij under when it starts a session's server, so the
+/// template's limits and priorities apply to the server and everything
+/// started in it
+pub fn wrapper(template: &Template) -> Vec<String> {
+    let mut wrapper = limits(template);
+    if let Some(nice) = template.nice {
+        wrapper.extend(["nice".to_string(), "-n".to_string(), nice.to_string()]);
+    }
+    if let Some(class) = template.ionice {
+        let class = match class {
+            IoClass::Realtime => "1",
+            IoClass::BestEffort => "2",
+            IoClass::Idle => "3",
+        };
+        wrapper.extend(["ionice".to_string(), "-c".to_string(), class.to_string()]);
+    }
+    if let Some(policy) = template.sched {
+        let policy = match policy {
+            SchedPolicy::Batch => "--batch",
+            SchedPolicy::Idle => "--idle",
+        };
+        wrapper.extend(["chrt".to_string(), policy.to_string(), "0".to_string()]);
+    }
+    wrapper
+}
+
+/// Gives the server the template's environment. Whatever `cmd` sets
+/// already (like the socket directory) is ours and stays.
+pub fn environment(template: &Template, cmd: &mut Command) {
+    let ours: Vec<String> = cmd
+        .get_envs()
+        .map(|(name, _)| name.to_string_lossy().into_owned())
+        .collect();
+    let unset = |name: &str| {
+        template
+            .unset_env
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            })
+    };
+    for (name, _) in env::vars_os() {
+        let name = name.to_string_lossy();
+        if unset(&name) && !ours.iter().any(|own| *own == name) {
+            cmd.env_remove(name.as_ref());
+        }
+    }
+    cmd.envs(&template.env);
+}
+
+/// The variables refreshed on every attach to a session started from
+/// `template`
+pub fn refreshed_env<'c>(config: &'c Config, template: Option<&'c Template>) -> Vec<&'c str> {
+    let mut names: Vec<&str> = config.refresh_env.iter().map(String::as_str).collect();
+    for name in template.iter().flat_map(|template| &template.refresh_env) {
+        if !names.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Where the refreshed variables are written for `session`'s shells to
+/// source. zellij has no way to change a running session's environment,
+/// so this is the next best thing. None for a name that isn't a file name
+/// by itself, which could point anywhere.
+pub fn env_file(session: &str) -> Option<PathBuf> {
+    sessions::check_name(session).ok()?;
+    runtime_dir().map(|dir| dir.join("env").join(format!("{}.sh", session)))
+}
+
+/// Writes the current values of `names` to the session's env file, as
+/// `export`s, or `unset`s for the ones that aren't set here
+pub fn refresh_env(session: &str, names: &[&str]) -> io::Result<()> {
+    sessions::check_name(session)?;
+    let path = env_file(session)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no runtime directory"))?;
+    let mut script = String::new();
+    for name in names {
+        match env::var(name) {
+            Ok(value) => {
+                let value = value.replace('\'', "'\\''");
+                script.push_str(&format!("export {}='{}'\n", name, value));
+            }
+            Err(_) => script.push_str(&format!("unset {}\n", name)),
+        }
+    }
+    if dryrun::pretend(format_args!(
+        "write to {}:\n{}",
+        path.display(),
+        script.trim_end()
+    )) {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, script)
+}
+
+/// `path` with a leading `~/` made into the home directory
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn limits(template: &Template) -> Vec<String> {
+    let mut properties = Vec::new();
+    if let Some(memory) = &template.memory_max {
+        properties.push(format!("MemoryMax={}", memory));
+    }
+    if let Some(cpu) = &template.cpu_quota {
+        properties.push(format!("CPUQuota={}", cpu));
+    }
+    if let Some(tasks) = template.tasks_max {
+        properties.push(format!("TasksMax={}", tasks));
+    }
+    if properties.is_empty() {
+        return Vec::new();
+    }
+    // A scope keeps the server in its cgroup even after it daemonizes
+    let mut wrapper: Vec<String> = ["systemd-run", "--user", "--scope", "--quiet"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    for property in properties {
+        wrapper.push("-p".to_string());
+        wrapper.push(property);
+    }
+    wrapper
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_files_stay_in_their_directory() {
+        for session in ["../escaped", "work/api", ".."] {
+            assert_eq!(env_file(session), None, "{:?}", session);
+            let err = refresh_env(session, &["HOME"]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        if let Some(dir) = runtime_dir() {
+            assert_eq!(env_file("work"), Some(dir.join("env").join("work.sh")));
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! What other Rust tools can build on without running `zellij-chooser`:
+//! mainly [`events::SessionEvents`], to hear about zellij sessions being
+//! created, renamed, killed, attached to and detached from as it happens.
+//! The binary is built on the same modules.
+
+pub mod events;
+pub mod sessions;
+pub mod snapshot;
+pub mod watch;
+pub mod zellij;
+
+// What those need, shared with the binary but not meant for anyone else
+#[doc(hidden)]
+pub mod clock;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod details;
+#[doc(hidden)]
+pub mod dryrun;
+#[doc(hidden)]
+pub mod listing;
+#[doc(hidden)]
+pub mod locale;
+#[doc(hidden)]
+pub mod procfs;
+#[doc(hidden)]
+pub mod state;
+#[doc(hidden)]
+pub mod store;
+#[doc(hidden)]
+pub mod theme;
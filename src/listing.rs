@@ -0,0 +1,318 @@
+use crate::procfs::Snapshot;
+use crate::sessions;
+use crate::state::State;
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Orders for a list of sessions
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    /// Alphabetically
+    Name,
+    /// Newest server first
+    Created,
+    /// Most recently attached to first
+    Recent,
+    /// Most CPU time used first (or in `top`, most CPU used lately)
+    Cpu,
+    /// Most memory used first
+    Memory,
+}
+
+/// What the picker's list can be split up by, under a heading each
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum GroupBy {
+    /// Also what grouping by `host` or `version` comes to now that those
+    /// are gone (every session listed is on this machine and speaks our
+    /// version), so a saved state that used one still loads
+    #[default]
+    #[serde(alias = "host", alias = "version")]
+    None,
+    /// The config's `[groups]`
+    Group,
+    /// Attached, detached or exited
+    Status,
+}
+
+/// The heading each session goes under for `group_by`, or nothing when
+/// the list isn't grouped
+pub fn group_labels(
+    group_by: GroupBy,
+    running: &[String],
+    dead: &[String],
+    groups: &BTreeMap<String, Vec<String>>,
+    socket_dir: &Path,
+) -> HashMap<String, String> {
+    if group_by == GroupBy::None {
+        return HashMap::new();
+    }
+    let label = |session: &String| -> String {
+        match group_by {
+            GroupBy::None => String::new(),
+            GroupBy::Group => groups
+                .iter()
+                .find(|(_, members)| members.contains(session))
+                .map_or_else(|| "no group".to_string(), |(group, _)| group.clone()),
+            GroupBy::Status if dead.contains(session) => "exited".to_string(),
+            GroupBy::Status if sessions::is_attached(socket_dir, session) => "attached".to_string(),
+            GroupBy::Status => "detached".to_string(),
+        }
+    };
+    running
+        .iter()
+        .chain(dead)
+        .map(|session| (session.clone(), label(session)))
+        .collect()
+}
+
+/// Which sessions to show: a glob (`ci-*`), or a regex between slashes
+/// (`/^ci-\d+$/`)
+#[derive(Debug, Clone)]
+pub struct Filter {
+    regex: Regex,
+    // A glob's wildcards become capture groups, numbered left to right
+    glob: bool,
+}
+
+impl FromStr for Filter {
+    type Err = regex::Error;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let (regex, glob) = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            Some(regex) => (regex.to_string(), false),
+            None => (glob_to_regex(pattern), true),
+        };
+        Regex::new(&regex).map(|regex| Filter { regex, glob })
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, session: &str) -> bool {
+        self.regex.is_match(session)
+    }
+
+    /// What `session` becomes under `replacement`, if it matches: `$1`-style
+    /// references for a regex, or for a glob, `*`/`?` standing for whatever
+    /// the pattern's wildcards matched, in order (`old-*` → `new-*`)
+    pub fn rename(&self, session: &str, replacement: &str) -> Option<String> {
+        if !self.matches(session) {
+            return None;
+        }
+        let template = if self.glob {
+            glob_replacement(replacement)
+        } else {
+            replacement.to_string()
+        };
+        Some(self.regex.replace(session, template.as_str()).into_owned())
+    }
+}
+
+fn glob_replacement(replacement: &str) -> String {
+    let mut template = String::new();
+    let mut group = 0;
+    for c in replacement.chars() {
+        match c {
+            '*' | '?' => {
+                group += 1;
+                template.push_str(&format!("${{{}}}", group));
+            }
+            '$' => template.push_str("$$"),
+            c => template.push(c),
+        }
+    }
+    template
+}
+
+// `*` and `?` are the only wildcards, each captured so a rename can reuse
+// them; `[...]` classes pass through as is
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut in_class = false;
+    for c in glob.chars() {
+        match c {
+            '[' if !in_class => {
+                in_class = true;
+                regex.push(c);
+            }
+            ']' if in_class => {
+                in_class = false;
+                regex.push(c);
+            }
+            _ if in_class => regex.push(c),
+            '*' => regex.push_str("(.*)"),
+            '?' => regex.push_str("(.)"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// Applies `--filter` and `--sort` to a list of sessions
+pub fn arrange(
+    mut sessions: Vec<String>,
+    filter: Option<&Filter>,
+    sort: Option<SortKey>,
+    socket_dir: &Path,
+    state: &State,
+) -> Vec<String> {
+    if let Some(filter) = filter {
+        sessions.retain(|session| filter.matches(session));
+    }
+    match sort {
+        None => (),
+        Some(SortKey::Name) => sessions.sort(),
+        Some(SortKey::Created) => sessions
+            .sort_by_cached_key(|session| Reverse(sessions::created_at(socket_dir, session))),
+        Some(SortKey::Recent) => sessions.sort_by_cached_key(|session| {
+            Reverse(
+                state
+                    .sessions
+                    .get(session)
+                    .and_then(|record| record.attaches.last().copied()),
+            )
+        }),
+        Some(key @ (SortKey::Cpu | SortKey::Memory)) => {
+            let snapshot = Snapshot::take();
+            sessions.sort_by_cached_key(|session| {
+                Reverse(
+                    snapshot
+                        .session_usage(&socket_dir.join(session))
+                        .map(|usage| match key {
+                            SortKey::Cpu => usage.cpu_ticks,
+                            _ => usage.rss_kib,
+                        }),
+                )
+            })
+        }
+    }
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groupings_that_are_gone_load_as_none() {
+        for saved in ["\"host\"", "\"version\"", "\"none\""] {
+            let group_by: GroupBy = serde_json::from_str(saved).unwrap();
+            assert_eq!(group_by, GroupBy::None);
+        }
+        let group_by: GroupBy = serde_json::from_str("\"status\"").unwrap();
+        assert_eq!(group_by, GroupBy::Status);
+    }
+
+    fn filter(pattern: &str) -> Filter {
+        pattern.parse().unwrap()
+    }
+
+    #[test]
+    fn globs_become_anchored_regexes() {
+        assert_eq!(glob_to_regex("a.b*"), r"^a\.b(.*)$");
+        assert_eq!(glob_to_regex("v?"), "^v(.)$");
+        assert_eq!(glob_to_regex("[*.]x"), "^[*.]x$");
+        assert_eq!(glob_to_regex("(x)+"), r"^\(x\)\+$");
+    }
+
+    #[test]
+    fn globs_match_whole_names() {
+        let ci = filter("ci-*");
+        assert!(ci.matches("ci-12"));
+        assert!(ci.matches("ci-"));
+        assert!(!ci.matches("old-ci-12"));
+        let one = filter("v?");
+        assert!(one.matches("v1"));
+        assert!(!one.matches("v"));
+        assert!(!one.matches("v10"));
+    }
+
+    #[test]
+    fn regex_metacharacters_in_globs_are_literal() {
+        for glob in ["a.b", "a+b", "(x)", "a|b", "^x$", "a{2}", r"a\b", "a$b"] {
+            assert!(filter(glob).matches(glob), "{:?} should match itself", glob);
+        }
+        assert!(!filter("a.b").matches("axb"));
+        assert!(!filter("a+b").matches("aab"));
+        assert!(!filter("a|b").matches("a"));
+        assert!(!filter("a{2}").matches("aa"));
+    }
+
+    #[test]
+    fn classes_in_globs_pass_through() {
+        let ab = filter("[ab]-*");
+        assert!(ab.matches("a-1"));
+        assert!(ab.matches("b-"));
+        assert!(!ab.matches("c-1"));
+        // Wildcards and metacharacters mean nothing inside one
+        assert!(filter("[*.]x").matches("*x"));
+        assert!(!filter("[*.]x").matches("ax"));
+        assert!("[ab".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn regexes_go_between_slashes() {
+        let ci = filter(r"/^ci-\d+$/");
+        assert!(ci.matches("ci-12"));
+        assert!(!ci.matches("ci-x"));
+        assert!(filter("/ci/").matches("old-ci-12"));
+        assert!("/(/".parse::<Filter>().is_err());
+        // Only a pattern both starting and ending with one
+        assert!(filter("/ci").matches("/ci"));
+    }
+
+    #[test]
+    fn glob_renames_reuse_what_the_wildcards_matched() {
+        assert_eq!(
+            filter("old-*").rename("old-12", "new-*").as_deref(),
+            Some("new-12")
+        );
+        assert_eq!(
+            filter("*-?").rename("web-3", "?_*").as_deref(),
+            Some("web_3")
+        );
+        assert_eq!(
+            filter("*.log").rename("app.log", "*").as_deref(),
+            Some("app")
+        );
+        // Wildcards beyond the pattern's have nothing to stand for
+        assert_eq!(
+            filter("old-*").rename("old-12", "*-*").as_deref(),
+            Some("12-")
+        );
+        // `$` is nothing special in a glob's replacement
+        assert_eq!(
+            filter("cost-*").rename("cost-5", "$*").as_deref(),
+            Some("$5")
+        );
+        assert_eq!(glob_replacement("$1-*"), "$$1-${1}");
+    }
+
+    #[test]
+    fn regex_renames_use_numbered_groups() {
+        assert_eq!(
+            filter(r"/^ci-(\d+)$/")
+                .rename("ci-12", "build-$1")
+                .as_deref(),
+            Some("build-12")
+        );
+        assert_eq!(
+            filter("/^(.*)-old$/").rename("api-old", "${1}2").as_deref(),
+            Some("api2")
+        );
+    }
+
+    #[test]
+    fn names_that_dont_match_arent_renamed() {
+        assert_eq!(filter("old-*").rename("new-12", "x-*"), None);
+        assert_eq!(filter("/^ci-(\\d+)$/").rename("ci-x", "build-$1"), None);
+        assert_eq!(filter("v?").rename("v10", "w?"), None);
+    }
+}
@@ -0,0 +1,167 @@
+use chrono::{Datelike, Local, TimeZone, Timelike};
+use std::env;
+use std::sync::OnceLock;
+
+/// How numbers and dates are written where we live
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    /// Between groups of three digits, if they're grouped at all
+    pub thousands: Option<&'static str>,
+    pub decimal: char,
+    pub dates: DateStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateStyle {
+    /// 2026-10-15 14:03
+    Iso,
+    /// 15.10.2026 14:03, with the separator given
+    DayMonthYear(char),
+    /// 10/15/2026 2:03 PM
+    MonthDayYear,
+}
+
+impl Locale {
+    /// What C and POSIX (and anything we can't make sense of) get
+    pub const POSIX: Locale = Locale {
+        thousands: None,
+        decimal: '.',
+        dates: DateStyle::Iso,
+    };
+
+    /// Makes sense of names like "de_DE.UTF-8", "fr_CA" or "en"
+    pub fn parse(name: &str) -> Locale {
+        let name = name.split(['.', '@']).next().unwrap_or_default();
+        let mut parts = name.split(['_', '-']);
+        let language = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let territory = parts.next().unwrap_or_default().to_ascii_uppercase();
+        let (thousands, decimal) = match (language.as_str(), territory.as_str()) {
+            ("de" | "it" | "fr", "CH") => (Some("'"), '.'),
+            ("en" | "ja" | "zh" | "ko" | "he" | "th" | "hi", _) => (Some(","), '.'),
+            (
+                "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl"
+                | "sr" | "vi",
+                _,
+            ) => (Some("."), ','),
+            (
+                "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "nn" | "no" | "uk" | "hu"
+                | "bg" | "lt" | "lv" | "et",
+                _,
+            ) => (Some("\u{a0}"), ','),
+            _ => return Locale::POSIX,
+        };
+        let dates = match (language.as_str(), territory.as_str()) {
+            ("en", "US") => DateStyle::MonthDayYear,
+            ("sv" | "lt" | "hu" | "ja" | "zh" | "ko", _) | ("fr", "CA") => DateStyle::Iso,
+            ("de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "nn" | "no" | "da" | "tr", _)
+            | ("uk" | "bg" | "lv" | "et" | "ro" | "sr" | "hr" | "sl", _)
+            | (_, "CH") => DateStyle::DayMonthYear('.'),
+            ("nl", _) => DateStyle::DayMonthYear('-'),
+            _ => DateStyle::DayMonthYear('/'),
+        };
+        Locale {
+            thousands,
+            decimal,
+            dates,
+        }
+    }
+
+    /// 1234567 as "1,234,567", "1.234.567" and so on
+    pub fn count(&self, n: u64) -> String {
+        let digits = n.to_string();
+        let Some(thousands) = self.thousands else {
+            return digits;
+        };
+        let mut out = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                out.push_str(thousands);
+            }
+            out.push(digit);
+        }
+        out
+    }
+
+    /// `x` with `places` decimals, grouped like `count`
+    pub fn decimal(&self, x: f64, places: usize) -> String {
+        let formatted = format!("{:.*}", places, x.abs());
+        let (whole, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+        let mut out = if x.is_sign_negative() && x != 0.0 {
+            "-".to_string()
+        } else {
+            String::new()
+        };
+        out.push_str(&self.count(whole.parse().unwrap_or(0)));
+        if !fraction.is_empty() {
+            out.push(self.decimal);
+            out.push_str(fraction);
+        }
+        out
+    }
+
+    /// `at` (Unix seconds) in the local timezone, to the minute
+    pub fn datetime(&self, at: u64) -> String {
+        let Some(at) = Local.timestamp_opt(at as i64, 0).single() else {
+            return at.to_string();
+        };
+        let (day, month, year) = (at.day(), at.month(), at.year());
+        match self.dates {
+            DateStyle::Iso => format!(
+                "{}-{:02}-{:02} {:02}:{:02}",
+                year,
+                month,
+                day,
+                at.hour(),
+                at.minute()
+            ),
+            DateStyle::DayMonthYear(sep) => format!(
+                "{:02}{sep}{:02}{sep}{} {:02}:{:02}",
+                day,
+                month,
+                year,
+                at.hour(),
+                at.minute(),
+                sep = sep
+            ),
+            DateStyle::MonthDayYear => {
+                let (pm, hour) = at.hour12();
+                format!(
+                    "{:02}/{:02}/{} {}:{:02} {}",
+                    month,
+                    day,
+                    year,
+                    hour,
+                    at.minute(),
+                    if pm { "PM" } else { "AM" }
+                )
+            }
+        }
+    }
+}
+
+static CURRENT: OnceLock<Locale> = OnceLock::new();
+
+// Like libc does, LC_ALL wins, then the category's own variable, then LANG.
+// Numbers and dates are one setting here, so LC_NUMERIC goes before LC_TIME.
+fn from_env() -> Locale {
+    ["LC_ALL", "LC_NUMERIC", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| Locale::parse(&value))
+        .unwrap_or(Locale::POSIX)
+}
+
+/// Settles the locale for the rest of the run: the configured one if
+/// there is one, otherwise the environment's
+pub fn init(configured: Option<&str>) {
+    let locale = match configured {
+        Some(name) => Locale::parse(name),
+        None => from_env(),
+    };
+    let _ = CURRENT.set(locale);
+}
+
+pub fn current() -> &'static Locale {
+    CURRENT.get().unwrap_or(&Locale::POSIX)
+}
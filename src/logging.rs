@@ -0,0 +1,81 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Where the log goes once we've daemonized, since stderr then belongs to
+/// whatever zellij draws
+pub fn log_file() -> Option<PathBuf> {
+    crate::state::state_dir().map(|dir| dir.join("zellij-chooser.log"))
+}
+
+struct Logger {
+    // Stderr until `to_file` switches it over
+    file: Mutex<Option<File>>,
+}
+
+static LOGGER: Logger = Logger {
+    file: Mutex::new(None),
+};
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // zellij's IPC warns about every read that times out, which is how
+        // probing a server that doesn't answer ends; that's only news to
+        // someone debugging
+        if metadata.target().starts_with("zellij_utils")
+            && metadata.level() > Level::Error
+            && log::max_level() < LevelFilter::Trace
+        {
+            return false;
+        }
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let level = match record.level() {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        };
+        let line = format!("[{} {}] {}\n", level, record.target(), record.args());
+        match &mut *self.file.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(file) => drop(file.write_all(line.as_bytes())),
+            None => drop(io::stderr().write_all(line.as_bytes())),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Logs to stderr: warnings and errors by default, steps with `verbose`,
+/// everything down to the IPC chatter with `debug`
+pub fn init(verbose: bool, debug: bool) {
+    let level = if debug {
+        LevelFilter::Trace
+    } else if verbose {
+        LevelFilter::Info
+    } else {
+        LevelFilter::Warn
+    };
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Sends the rest of the log to `log_file()`, appending
+pub fn to_file() -> io::Result<()> {
+    let path = log_file().ok_or_else(|| io::Error::other("no state directory"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *LOGGER.file.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+    Ok(())
+}
@@ -1,59 +1,1733 @@
-use fork::{daemon, Fork};
-use rustyline::{
-    error::ReadlineError,
-    // validate::{ValidationContext, ValidationResult, Validator},
-    Editor,
-    Result as RustyResult,
-};
-use rustyline_derive::{Completer, Helper, Highlighter, Hinter};
+mod backend;
+mod bell;
+mod cli;
+mod daemon;
+mod demo;
+mod doctor;
+mod failure;
+mod handoff;
+mod hooks;
+mod idle;
+mod launcher;
+mod logging;
+mod manifest;
+mod matcher;
+mod names;
+mod picker;
+mod preview;
+mod ranking;
+mod refresh;
+mod remote;
+mod signals;
+mod symbols;
+mod tmux;
+mod top;
+mod tty;
+
+use backend::{AfterAttach, SessionBackend, ZellijBackend};
+use cli::{CliArgs, Command as CliCommand, SetAction};
+use config::{Config, Template, ThemeBase};
+use failure::Failure;
+use matcher::Matcher;
+use picker::{interactive_select, rank_all, Outcome, PickerOptions, Selection};
+use preview::TabSummary;
+use ranking::Ranker;
+use sessions::{get_dead_sessions, get_sessions, wait_for_socket};
+use snapshot::Snapshot;
+use state::State;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::ffi::OsStr;
-use std::os::unix::fs::FileTypeExt;
-use std::process::{self, Command};
-use std::{fs, io};
-use zellij_utils::{
-    consts::ZELLIJ_SOCK_DIR,
-    envs,
-    interprocess::local_socket::LocalSocketStream,
-    ipc::{ClientToServerMsg, IpcReceiverWithContext, IpcSenderWithContext, ServerToClientMsg},
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use theme::Theme;
+use zellij::{Feature, Zellij};
+use zellij_chooser::{
+    clock, config, details, dryrun, events, listing, locale, procfs, sessions, snapshot, state,
+    store, theme, watch, zellij,
 };
 
 fn main() {
-    // It seems helpful to protect the user from spawning a nested Zellij session
-    let _ = env::vars_os().into_iter().map(|v| {
-        if v.0.into_string().unwrap().contains("ZELLIJ") {
-            std::process::exit(-1);
+    if let Err(failure) = run() {
+        failure.exit();
+    }
+}
+
+fn run() -> Result<(), Failure> {
+    let opts = CliArgs::from_env();
+    logging::init(opts.verbose, opts.debug);
+    clock::init(opts.now, opts.seed.or(opts.demo.then_some(0)));
+    if opts.dry_run {
+        dryrun::enable();
+    }
+    signals::restore_terminal_on_exit();
+    let mut config = config::load(opts.config.as_deref()).unwrap_or_else(|err| {
+        eprintln!("Ignoring the config file, it couldn't be read: {}", err);
+        Config::default()
+    });
+    // With nobody attaching, it's `bell.detached` that announces a new
+    // session, once, where `bell.created` otherwise would
+    if let Some(CliCommand::New { detached: true, .. }) = opts.command {
+        config.bell.created = config.bell.detached;
+    }
+    symbols::init(config.symbols);
+    locale::init(config.locale.as_deref());
+    if config.theme.is_customized() {
+        let theme = Theme::from_config(&config.theme);
+        for warning in theme.lint(config.theme.base == ThemeBase::Colorblind) {
+            eprintln!("Warning: {}", warning);
         }
+    }
+    let zellij_bin = opts
+        .zellij_bin
+        .clone()
+        .or_else(|| config.zellij_bin.clone())
+        .unwrap_or_else(|| PathBuf::from("zellij"));
+    let zellij = Zellij::new(
+        zellij_bin,
+        opts.zellij_args.clone(),
+        opts.socket_dir.clone(),
+    );
+    let socket_dir = sessions::socket_dir(opts.socket_dir.as_deref());
+    let backend = ZellijBackend::new(&zellij, &socket_dir, &config);
+    store::init(config.state_backend);
+    let mut state = State::load().unwrap_or_else(|err| {
+        eprintln!(
+            "Starting with a blank state, the saved one couldn't be read: {}",
+            err
+        );
+        State::default()
     });
 
-    // ToDo
-    // Check if the client supplied an argv parameter for the session name they want
-    let session: Option<String> = env::args().nth(1_usize);
-    let running_sessions = match get_sessions() {
-        Err(err) if io::ErrorKind::NotFound != err => exit_zellij_not_found(),
+    // The local zellij doesn't matter for someone else's sessions
+    if let Some(host) = &opts.remote {
+        let bin = opts.zellij_bin.as_ref().map_or_else(
+            || "zellij".to_string(),
+            |bin| bin.to_string_lossy().into_owned(),
+        );
+        let extra_args = opts
+            .zellij_args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let remote = remote::Remote::new(host.clone(), bin, extra_args);
+        return remote::run(&remote, &opts, &config, theme(&opts, &config));
+    }
+    // Before anything that'd give up on a broken zellij or socket directory
+    if let Some(CliCommand::Doctor { yes }) = opts.command {
+        return doctor::run(&zellij, &socket_dir, yes);
+    }
+    match zellij.version_number() {
+        Err(_) => return Err(zellij_not_found(&zellij)),
+        Ok(version) if version != zellij::PROTOCOL_VERSION => {
+            // Servers of another version keep their sockets in a different
+            // directory, and the IPC messages we'd send them differ anyway
+            let mismatch = format!(
+                "{} is zellij {}, but zellij-chooser speaks the zellij {} protocol; \
+                 its sessions won't show up and attaching may misbehave.",
+                zellij.bin.display(),
+                version,
+                zellij::PROTOCOL_VERSION
+            );
+            if !opts.ignore_version_mismatch {
+                return Err(Failure::Zellij(format!(
+                    "{}\nRerun with --ignore-version-mismatch to try anyway.",
+                    mismatch
+                )));
+            }
+            eprintln!("{}", mismatch);
+        }
+        Ok(_) => (),
+    }
+    sessions::init_resurrection(&zellij);
+    if opts.demo {
+        return demo::run(&zellij, &config, theme(&opts, &config));
+    }
+    let probing = Instant::now();
+    let running_sessions = match get_sessions(&socket_dir) {
+        _ if opts.stdin => {
+            if opts.command.is_some() {
+                return Err(Failure::Other(
+                    "--stdin is only for picking a session, not with a subcommand".to_string(),
+                ));
+            }
+            tty::take_stdin_names().map_err(|err| {
+                Failure::Other(format!("Couldn't read session names from stdin: {}", err))
+            })?
+        }
+        Err(err) if io::ErrorKind::NotFound != err => {
+            return Err(Failure::Other(format!(
+                "Couldn't list the sessions in {}: {} (`zellij-chooser doctor` can look into it)",
+                socket_dir.display(),
+                io::Error::from(err)
+            )))
+        }
         Err(_) => Vec::<String>::new(),
-        Ok(sessions) => sessions,
+        Ok(sessions) => {
+            if probing.elapsed() >= SLOW_PROBING {
+                bell::alert(config.bell.probes_done, "Every session has been probed");
+            }
+            sessions
+        }
     };
 
-    match session.clone() {
-        None => {
-            let _ = interactive_select(&running_sessions);
+    match opts.command.clone() {
+        Some(CliCommand::New {
+            name,
+            detached,
+            template,
+            layout,
+            cwd,
+        }) => {
+            let mut template = resolve_template(&config, template.as_deref())?;
+            if layout.is_some() {
+                template.layout = layout;
+            }
+            if cwd.is_some() {
+                template.cwd = cwd;
+            }
+            let name = name.unwrap_or_else(|| {
+                let dead_sessions = get_dead_sessions(&running_sessions);
+                let name = names::memorable(running_sessions.iter().chain(&dead_sessions));
+                println!("New session: {}", name);
+                name
+            });
+            new_session(
+                &backend,
+                &config,
+                &mut state,
+                &running_sessions,
+                name,
+                detached,
+                &template,
+            )?;
+        }
+        Some(CliCommand::Rename {
+            from,
+            to,
+            pattern,
+            replace,
+        }) => {
+            renaming_supported(&zellij)?;
+            let dry_run = opts.dry_run;
+            let bulk = pattern.is_some();
+            let plan: Vec<(String, String)> = match (from, to, pattern, replace) {
+                (Some(from), Some(to), _, _) => vec![(from, to)],
+                (_, _, Some(pattern), Some(replace)) => running_sessions
+                    .iter()
+                    .filter_map(|s| pattern.rename(s, &replace).map(|to| (s.clone(), to)))
+                    .filter(|(from, to)| from != to)
+                    .collect(),
+                _ => Vec::new(),
+            };
+            match plan.as_slice() {
+                [] => return Err(Failure::NotFound("No running session matches".to_string())),
+                [(from, to)] if !bulk && !dry_run => {
+                    rename_session(&zellij, &mut state, &running_sessions, from, to)?;
+                }
+                plan => {
+                    if !rename_many(&zellij, &mut state, &running_sessions, plan, dry_run) {
+                        return Err(Failure::Other(
+                            "Not every session could be renamed".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+        Some(CliCommand::Annotate {
+            session,
+            tags,
+            untags,
+            note,
+            cwd,
+        }) => {
+            let record = state.sessions.entry(session).or_default();
+            record.tags.retain(|tag| !untags.contains(tag));
+            for tag in tags {
+                if !record.tags.contains(&tag) {
+                    record.tags.push(tag);
+                }
+            }
+            if let Some(note) = note {
+                record.notes = note;
+            }
+            if cwd.is_some() {
+                record.cwd = cwd;
+            }
+            save_state(&state);
+        }
+        Some(CliCommand::Adopt {
+            sessions,
+            tags,
+            sets,
+        }) => {
+            let sessions = if sessions.is_empty() {
+                untracked(&state, &running_sessions)
+            } else {
+                for session in &sessions {
+                    if !running_sessions.contains(session) {
+                        return Err(Failure::NotFound(format!(
+                            "There's no running session called \"{}\"",
+                            session
+                        )));
+                    }
+                }
+                sessions
+            };
+            if sessions.is_empty() {
+                println!("Every running session is tracked already");
+            }
+            for session in &sessions {
+                adopt(&socket_dir, &mut state, session, &tags, &sets);
+            }
+            save_state(&state);
+        }
+        Some(CliCommand::List {
+            include_dead,
+            json: true,
+            ..
+        }) => {
+            let arrange = |sessions| {
+                listing::arrange(
+                    sessions,
+                    opts.filter.as_ref(),
+                    opts.sort,
+                    &socket_dir,
+                    &state,
+                )
+            };
+            let dead = include_dead.then(|| arrange(get_dead_sessions(&running_sessions)));
+            let snapshot = Snapshot::take(
+                &socket_dir,
+                &arrange(running_sessions.clone()),
+                dead.as_deref(),
+            );
+            let json = serde_json::to_string_pretty(&snapshot)
+                .map_err(|err| Failure::Other(format!("Couldn't write the snapshot: {}", err)))?;
+            println!("{}", json);
+        }
+        Some(CliCommand::Diff { snapshot }) => {
+            let mut before = Snapshot::load(&snapshot).map_err(|err| {
+                Failure::Other(format!(
+                    "Couldn't read the snapshot {}: {}",
+                    snapshot.display(),
+                    err
+                ))
+            })?;
+            let mut running = running_sessions.clone();
+            let mut dead = before
+                .include_dead
+                .then(|| get_dead_sessions(&running_sessions));
+            if let Some(filter) = &opts.filter {
+                before.sessions.retain(|entry| filter.matches(&entry.name));
+                running.retain(|s| filter.matches(s));
+                if let Some(dead) = &mut dead {
+                    dead.retain(|s| filter.matches(s));
+                }
+            }
+            let now = Snapshot::take(&socket_dir, &running, dead.as_deref());
+            let changes = snapshot::diff(&before, &now);
+            println!("{}", snapshot::render(&before, &changes, now.taken));
+        }
+        Some(CliCommand::List {
+            include_dead,
+            watch,
+            ..
+        }) => {
+            let arrange = |sessions| {
+                listing::arrange(
+                    sessions,
+                    opts.filter.as_ref(),
+                    opts.sort,
+                    &socket_dir,
+                    &state,
+                )
+            };
+            let theme = theme(&opts, &config);
+            let paint = |text: &str, color: fn(&Theme) -> theme::Rgb| match &theme {
+                Some(theme) => theme::paint(text, color(theme)),
+                None => text.to_string(),
+            };
+            let print = |running_sessions: &[String]| {
+                for session in arrange(running_sessions.to_vec()) {
+                    if backend.is_attached(&session) {
+                        println!("{}", paint(&session, |t| t.attached));
+                    } else {
+                        println!("{}", paint(&session, |t| t.detached));
+                    }
+                }
+                for (session, status) in backend.troubled(running_sessions) {
+                    if opts.filter.as_ref().is_some_and(|f| !f.matches(&session)) {
+                        continue;
+                    }
+                    let trouble = status.trouble().unwrap_or_default();
+                    println!(
+                        "{}",
+                        paint(&format!("{} ({})", session, trouble), |t| t.dead)
+                    );
+                }
+                if include_dead {
+                    for session in arrange(get_dead_sessions(running_sessions)) {
+                        println!("{}", paint(&format!("{} (dead)", session), |t| t.dead));
+                    }
+                }
+            };
+            if watch {
+                let mut watcher = watch::Watcher::new(&socket_dir, WATCH_PROBE_EVERY);
+                loop {
+                    if io::stdout().is_terminal() {
+                        print!("\x1b[H\x1b[2J");
+                    }
+                    print(watcher.running());
+                    while watcher.wait(WATCH_PROBE_EVERY).is_none() {}
+                }
+            }
+            print(&running_sessions);
+        }
+        Some(CliCommand::Events { interval, json }) => {
+            events::run(
+                &socket_dir,
+                opts.filter.as_ref(),
+                Duration::from_secs(interval.max(1)),
+                json,
+            )
+            .map_err(|err| Failure::Other(format!("Couldn't print the events: {}", err)))?;
+        }
+        Some(CliCommand::Daemon {
+            socket,
+            interval,
+            query,
+        }) => {
+            let socket = socket
+                .or_else(daemon::default_socket)
+                .ok_or_else(|| Failure::Other("Nowhere to put the daemon's socket".to_string()))?;
+            match query {
+                Some(request) => {
+                    let answer = daemon::query(&socket, &request).map_err(|err| {
+                        Failure::Other(format!(
+                            "Couldn't ask the daemon on {}: {}",
+                            socket.display(),
+                            err
+                        ))
+                    })?;
+                    println!("{}", answer);
+                }
+                None => daemon::run(&socket_dir, &socket, Duration::from_secs(interval.max(1)))
+                    .map_err(|err| Failure::Other(format!("The daemon stopped: {}", err)))?,
+            }
+        }
+        Some(CliCommand::Top { interval, once }) => {
+            top::run(
+                &socket_dir,
+                &state,
+                opts.filter.as_ref(),
+                opts.sort,
+                Duration::from_secs(interval.max(1)),
+                once,
+            )
+            .map_err(|err| Failure::Other(format!("Couldn't show the sessions: {}", err)))?;
+        }
+        // Seen to before the version check
+        Some(CliCommand::Doctor { .. }) => unreachable!(),
+        Some(CliCommand::Stats { json }) => stats(&opts, &state, &running_sessions, json)?,
+        Some(CliCommand::Prune { interactive, days }) => {
+            prune(
+                &backend,
+                &socket_dir,
+                &state,
+                &running_sessions,
+                days.unwrap_or(config.idle.after_days),
+                interactive,
+            )?;
+            bell::alert(config.bell.pruned, "Pruning is done");
+        }
+        Some(CliCommand::Set { action }) => {
+            manage_sets(&mut state, &config, &opts, &running_sessions, action)?
+        }
+        Some(CliCommand::Kill { sessions }) => {
+            let names = expand_sets(&state, &config, &sessions)?;
+            let mut failed = false;
+            for name in &names {
+                if !running_sessions.contains(name) {
+                    eprintln!("{} isn't running", name);
+                    failed = true;
+                } else if let Err(err) = backend.kill(name) {
+                    eprintln!("Couldn't kill {}: {}", name, err);
+                    failed = true;
+                }
+            }
+            if failed {
+                return Err(Failure::Other(
+                    "Not every session could be killed".to_string(),
+                ));
+            }
+        }
+        Some(CliCommand::Open { sessions, template }) => {
+            let names = expand_sets(&state, &config, &sessions)?;
+            let template = resolve_template(&config, template.as_deref())?;
+            let mut failed = false;
+            for name in &names {
+                if running_sessions.contains(name) {
+                    println!("{} is already running", name);
+                    continue;
+                }
+                match backend::create_unless_running(&backend, name, &template) {
+                    Ok(true) => {
+                        state.record_template(name, &template);
+                        println!("Started {}", name);
+                    }
+                    Ok(false) => println!("{} is already running", name),
+                    Err(err) => {
+                        eprintln!("Couldn't start {}: {}", name, err);
+                        failed = true;
+                    }
+                }
+            }
+            save_state(&state);
+            if failed {
+                return Err(Failure::Other(
+                    "Not every session could be started".to_string(),
+                ));
+            }
+        }
+        Some(CliCommand::Up {
+            group: name,
+            template,
+        }) => {
+            let template = resolve_template(&config, template.as_deref())?;
+            let mut failed = false;
+            for session in group(&config, &name)? {
+                if running_sessions.contains(session) {
+                    println!("{} is up", session);
+                    continue;
+                }
+                match backend::create_unless_running(&backend, session, &template) {
+                    Ok(true) => {
+                        state.record_template(session, &template);
+                        println!("{} started", session);
+                    }
+                    Ok(false) => println!("{} is up", session),
+                    Err(err) => {
+                        eprintln!("Couldn't start {}: {}", session, err);
+                        failed = true;
+                    }
+                }
+            }
+            save_state(&state);
+            if failed {
+                return Err(Failure::Other(format!("Not all of {} is up", name)));
+            }
         }
-        Some(session_name) => match try_joining(&session_name, &running_sessions) {
-            Ok(_) => (),
-            Err(_) => {
-                spawn(session_name).expect("This should be infallible");
+        Some(CliCommand::Down { group: name }) => {
+            let mut failed = false;
+            for session in group(&config, &name)? {
+                if !running_sessions.contains(session) {
+                    continue;
+                }
+                match backend.kill(session) {
+                    Ok(()) => println!("{} killed", session),
+                    Err(err) => {
+                        eprintln!("Couldn't kill {}: {}", session, err);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                return Err(Failure::Other(format!("Not all of {} is down", name)));
+            }
+        }
+        Some(CliCommand::ImportTmux { sessions }) => {
+            import_tmux(&backend, &running_sessions, sessions, opts.dry_run)?
+        }
+        Some(CliCommand::Purge { name, all_dead }) => {
+            let dead_sessions = get_dead_sessions(&running_sessions);
+            let names = match name {
+                _ if all_dead => dead_sessions,
+                Some(name) if dead_sessions.contains(&name) => vec![name],
+                Some(name) if running_sessions.contains(&name) => {
+                    return Err(Failure::Other(format!(
+                        "\"{}\" is still running, only exited sessions can be purged",
+                        name
+                    )));
+                }
+                Some(name) => {
+                    return Err(Failure::NotFound(format!(
+                        "There's no resurrectable session called \"{}\"",
+                        name
+                    )));
+                }
+                None => Vec::new(),
+            };
+            let mut failed = false;
+            for name in &names {
+                if let Err(err) = purge_session(name) {
+                    eprintln!("{}", err);
+                    failed = true;
+                }
             }
+            bell::alert(config.bell.purged, "Purging is done");
+            if failed {
+                return Err(Failure::Other(
+                    "Not every session could be purged".to_string(),
+                ));
+            }
+        }
+        Some(CliCommand::MoveTab {
+            from,
+            to,
+            tab,
+            copy,
+        }) => {
+            move_tab(&zellij, &running_sessions, &from, &to, tab.as_deref(), copy)?;
+        }
+        Some(CliCommand::BreakTab {
+            session,
+            new_session,
+            tab,
+            copy,
+        }) => {
+            break_tab(
+                &zellij,
+                &backend,
+                &running_sessions,
+                &session,
+                &new_session,
+                tab.as_deref(),
+                copy,
+            )?;
+        }
+        Some(CliCommand::Clone { source, name }) => {
+            let dead_sessions = get_dead_sessions(&running_sessions);
+            let source = if dead_sessions.contains(&source) {
+                source
+            } else {
+                expand_prefix(&source, &running_sessions, opts.exact, &config.aliases)?
+            };
+            let name = clone_session(
+                &zellij,
+                &backend,
+                &config,
+                &mut state,
+                &running_sessions,
+                &source,
+                name,
+            )?;
+            println!("Started \"{}\", a clone of \"{}\"", name, source);
+        }
+        Some(CliCommand::Attach { session, tab }) => {
+            let opts = CliArgs {
+                session: Some(session),
+                tab: tab.or_else(|| opts.tab.clone()),
+                command: None,
+                ..opts.clone()
+            };
+            choose(
+                &zellij,
+                &backend,
+                &socket_dir,
+                &opts,
+                &config,
+                &mut state,
+                running_sessions,
+            )?
+        }
+        None => choose(
+            &zellij,
+            &backend,
+            &socket_dir,
+            &opts,
+            &config,
+            &mut state,
+            running_sessions,
+        )?,
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn new_session(
+    backend: &dyn SessionBackend,
+    config: &Config,
+    state: &mut State,
+    running_sessions: &[String],
+    name: String,
+    detached: bool,
+    template: &Template,
+) -> Result<(), Failure> {
+    if !detached {
+        refuse_nesting()?;
+    }
+    if try_joining(&name, running_sessions).is_ok() {
+        println!("Session \"{}\" is already running", name);
+    } else {
+        let created = backend::create_unless_running(backend, &name, template).map_err(|err| {
+            Failure::from_zellij(err, format!("Couldn't create session \"{}\"", name))
+        })?;
+        if created {
+            state.record_template(&name, template);
+        } else {
+            println!("Session \"{}\" was started elsewhere meanwhile", name);
+        }
+    }
+    if !detached {
+        state.record_attach(&name, clock::now());
+        save_state(state);
+        connect(backend, config, state, &name, None)?;
+    } else {
+        save_state(state);
+    }
+    Ok(())
+}
+
+// Picks a session (from argv or interactively) and attaches to it
+#[allow(clippy::too_many_arguments)]
+fn choose(
+    zellij: &Zellij,
+    backend: &dyn SessionBackend,
+    socket_dir: &Path,
+    opts: &CliArgs,
+    config: &Config,
+    state: &mut State,
+    mut running_sessions: Vec<String>,
+) -> Result<(), Failure> {
+    let mut print_to = if opts.print {
+        let original = tty::divert_stdout()
+            .map_err(|err| Failure::Other(format!("Couldn't set up the terminal: {}", err)))?;
+        Some(original)
+    } else {
+        None
+    };
+    let selection = match (opts.session.clone(), opts.select_or_create.clone()) {
+        (Some(name), _) if opts.fuzzy_arg => {
+            let name = config.unalias(&name);
+            match_fuzzily(backend, opts, config, state, name, &running_sessions)?
+        }
+        (Some(name), _) => Selection {
+            name: expand_prefix(&name, &running_sessions, opts.exact, &config.aliases)?,
+            detach_others: false,
+            queries: Vec::new(),
+            from_blueprint: false,
         },
+        (None, Some(wanted)) => {
+            let wanted = config.unalias(&wanted).to_string();
+            let matcher = Matcher::new(&config.matching);
+            let best = running_sessions
+                .iter()
+                .filter_map(|session| matcher.score(&wanted, session).map(|s| (s, session)))
+                .max_by_key(|(score, _)| *score);
+            Selection {
+                name: best.map_or(wanted, |(_, session)| session.clone()),
+                detach_others: false,
+                queries: Vec::new(),
+                from_blueprint: false,
+            }
+        }
+        (None, None) => pick_interactively(
+            zellij,
+            backend,
+            socket_dir,
+            opts,
+            config,
+            state,
+            &mut running_sessions,
+        )?,
     };
-    connect(session.unwrap());
+    let running_sessions = &running_sessions;
+    for query in &selection.queries {
+        state.record_query(query);
+    }
+    if let Some(out) = &mut print_to {
+        save_state(state);
+        return writeln!(out, "{}", selection.name)
+            .map_err(|err| Failure::Other(format!("Couldn't print the selection: {}", err)));
+    }
+    if opts.export_env {
+        save_state(state);
+        let err = exec_shell_with_selection(&selection.name);
+        return Err(Failure::Other(format!(
+            "Couldn't start your shell: {}",
+            err
+        )));
+    }
+    make_running(
+        backend,
+        config,
+        state,
+        running_sessions,
+        &selection,
+        opts.detach_others,
+    )?;
+    let pick_tab = opts.pick_tab
+        || state
+            .template_of(config, &selection.name)
+            .is_some_and(|template| template.pick_tab);
+    let tab = match &opts.tab {
+        Some(tab) => Some(tab_position(zellij, &selection.name, tab)?),
+        None if pick_tab && io::stdin().is_terminal() => {
+            pick_tab_to_attach(zellij, &selection.name)?
+        }
+        None => None,
+    };
+    state.record_attach(&selection.name, clock::now());
+    save_state(state);
+    connect(backend, config, state, &selection.name, tab)?;
     // At this point, we should have checked against (1) broken zellij installations,
     // (2) a session name passed from STDIN, where we would have joined
+    Ok(())
+}
+
+// Sees to it that there's a session to attach to by the selected name,
+// creating it (or starting it over from its blueprint) if need be. An
+// exited one is left alone: attaching is what brings it back.
+fn make_running(
+    backend: &dyn SessionBackend,
+    config: &Config,
+    state: &mut State,
+    running_sessions: &[String],
+    selection: &Selection,
+    detach_others: bool,
+) -> Result<(), Failure> {
+    match try_joining(&selection.name, running_sessions) {
+        Ok(_) if detach_others || selection.detach_others => {
+            if let Err(err) = backend.detach_others(&selection.name) {
+                eprintln!("Couldn't detach the other clients: {}", err);
+            }
+        }
+        Ok(_) => (),
+        Err(_) if selection.from_blueprint => {
+            recreate_from_blueprint(backend, &selection.name).map_err(|err| {
+                Failure::from_zellij(err, format!("Couldn't start \"{}\" over", selection.name))
+            })?;
+        }
+        Err(_) if get_dead_sessions(running_sessions).contains(&selection.name) => (),
+        Err(_) => {
+            let template = resolve_template(config, None)?;
+            let created = backend::create_unless_running(backend, &selection.name, &template)
+                .map_err(|err| {
+                    Failure::from_zellij(
+                        err,
+                        format!("Couldn't create session \"{}\"", selection.name),
+                    )
+                })?;
+            if created {
+                state.record_template(&selection.name, &template);
+            }
+        }
+    }
+    Ok(())
+}
+
+// The running session `name` is a prefix of, when there's exactly one and
+// no session is called `name` outright (or aliased as `name`). Several
+// candidates are listed and we bail rather than guess.
+fn expand_prefix(
+    name: &str,
+    running_sessions: &[String],
+    exact: bool,
+    aliases: &BTreeMap<String, String>,
+) -> Result<String, Failure> {
+    if let Some(session) = aliases.get(name) {
+        return Ok(session.clone());
+    }
+    if exact || running_sessions.iter().any(|s| s == name) {
+        return Ok(name.to_string());
+    }
+    // A prefix of an alias counts as one of the session it stands for
+    let by_alias = aliases
+        .iter()
+        .filter(|(alias, session)| alias.starts_with(name) && running_sessions.contains(session))
+        .map(|(_, session)| session);
+    let mut candidates: Vec<&String> = Vec::new();
+    for session in running_sessions
+        .iter()
+        .filter(|s| s.starts_with(name))
+        .chain(by_alias)
+    {
+        if !candidates.contains(&session) {
+            candidates.push(session);
+        }
+    }
+    match candidates.as_slice() {
+        [] => Ok(name.to_string()),
+        [only] => Ok(only.to_string()),
+        several => Err(ambiguous(name, several)),
+    }
+}
+
+fn ambiguous(name: &str, candidates: &[impl AsRef<str>]) -> Failure {
+    let mut msg = format!("\"{}\" could be any of these sessions:\n", name);
+    for session in candidates {
+        msg.push_str(&format!("    {}\n", session.as_ref()));
+    }
+    msg.push_str(&format!(
+        "Type more of the name, or pass --exact to create \"{}\"",
+        name
+    ));
+    Failure::Other(msg)
+}
+
+// The running session `name` fuzzily matches, when it matches just one (or
+// is one outright) and `name` itself when it matches none. Several are
+// offered in a picker of their own rather than guessed between.
+fn match_fuzzily(
+    backend: &dyn SessionBackend,
+    opts: &CliArgs,
+    config: &Config,
+    state: &State,
+    name: &str,
+    running_sessions: &[String],
+) -> Result<Selection, Failure> {
+    let selection = |name: &str| Selection {
+        name: name.to_string(),
+        detach_others: false,
+        queries: Vec::new(),
+        from_blueprint: false,
+    };
+    if running_sessions.iter().any(|s| s == name) {
+        return Ok(selection(name));
+    }
+    let matcher = Matcher::new(&config.matching);
+    let candidates: Vec<String> = running_sessions
+        .iter()
+        .filter(|session| matcher.score(name, session).is_some())
+        .cloned()
+        .collect();
+    match candidates.as_slice() {
+        [] => return Ok(selection(name)),
+        [only] => return Ok(selection(only)),
+        // Nobody to ask
+        several if !io::stdin().is_terminal() || !io::stdout().is_terminal() => {
+            return Err(ambiguous(name, several));
+        }
+        _ => (),
+    }
+    println!("\"{}\" matches several sessions:", name);
+    let ranker = Ranker::new(&matcher, &config.ranking, state, clock::now());
+    loop {
+        let options = PickerOptions {
+            theme: theme(opts, config),
+            explain_ranking: opts.explain_ranking,
+            keys: config.keys.clone(),
+            ctrl_c: config.ctrl_c,
+            attached: candidates
+                .iter()
+                .filter(|s| backend.is_attached(s))
+                .cloned()
+                .collect(),
+            ..PickerOptions::default()
+        };
+        let no_preview = |_: &str| -> Option<String> { None };
+        match interactive_select(&candidates, &[], &ranker, options, &no_preview, &no_preview)? {
+            Outcome::Attach(selection) => return Ok(selection),
+            Outcome::Refresh => (),
+            _ => println!("Only attaching works here; run the picker on its own for the rest"),
+        }
+    }
+}
+
+// One line on where things stand, from what's known already: "zellij
+// 0.31.4 | 4 live, 2 dead | profile work | last: api, 3h ago"
+fn summary(
+    zellij: &Zellij,
+    opts: &CliArgs,
+    state: &State,
+    running_sessions: &[String],
+    dead_sessions: &[String],
+) -> String {
+    let separator = format!(" {} ", symbols::current().box_side);
+    let count = |n: usize| locale::current().count(n as u64);
+    let mut parts = Vec::new();
+    if let Ok(version) = zellij.version_number() {
+        parts.push(format!("zellij {}", version));
+    }
+    parts.push(format!(
+        "{} live, {} dead",
+        count(running_sessions.len()),
+        count(dead_sessions.len())
+    ));
+    if let Some(profile) = opts.config.as_deref().and_then(Path::file_stem) {
+        parts.push(format!("profile {}", profile.to_string_lossy()));
+    }
+    let last = state
+        .sessions
+        .iter()
+        .filter_map(|(name, record)| Some((record.attaches.last()?, name)))
+        .max();
+    if let Some((at, name)) = last {
+        parts.push(format!(
+            "last: {}, {}",
+            name,
+            details::ago(*at, clock::now())
+        ));
+    }
+    parts.join(&separator)
+}
+
+// Exited sessions to offer next to the running ones; none when the
+// candidates came from --stdin
+fn offered_dead_sessions(opts: &CliArgs, running_sessions: &[String]) -> Vec<String> {
+    if opts.stdin {
+        return Vec::new();
+    }
+    get_dead_sessions(running_sessions)
+}
+
+// The theme to draw with, unless color is off: by --no-color, by $NO_COLOR
+// (https://no-color.org), or because stdout isn't a terminal
+fn theme(opts: &CliArgs, config: &Config) -> Option<Theme> {
+    let use_color =
+        !opts.no_color && env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal();
+    Some(Theme::from_config(&config.theme)).filter(|_| use_color)
+}
+
+// Keeps reopening the picker until it comes back with something to attach
+// to, carrying out whatever else was asked for in between
+#[allow(clippy::too_many_arguments)]
+fn pick_interactively(
+    zellij: &Zellij,
+    backend: &dyn SessionBackend,
+    socket_dir: &Path,
+    opts: &CliArgs,
+    config: &Config,
+    state: &mut State,
+    running_sessions: &mut Vec<String>,
+) -> Result<Selection, Failure> {
+    let matcher = Matcher::new(&config.matching);
+    let pinned = match &opts.manifest {
+        Some(path) => manifest::load(path).map_err(|err| {
+            Failure::Other(format!(
+                "Couldn't read the manifest {}: {}",
+                path.display(),
+                err
+            ))
+        })?,
+        None => Vec::new(),
+    };
+    if opts.first || opts.last || !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return pick_without_prompting(
+            socket_dir,
+            opts,
+            config,
+            state,
+            running_sessions,
+            &matcher,
+            pinned,
+        );
+    }
+    let everything: Vec<String> = running_sessions
+        .iter()
+        .cloned()
+        .chain(offered_dead_sessions(opts, running_sessions))
+        .collect();
+    let reminder = (!opts.quiet)
+        .then(|| idle::reminder(&config.idle, &everything, socket_dir, state, clock::now()))
+        .flatten();
+    if let Some(reminder) = reminder {
+        println!("{}", reminder);
+        save_state(state);
+    }
+    let troubled = backend.troubled(running_sessions);
+    if !troubled.is_empty() {
+        let described: Vec<String> = troubled
+            .iter()
+            .map(|(session, status)| {
+                format!("{} ({})", session, status.trouble().unwrap_or_default())
+            })
+            .collect();
+        println!(
+            "Left out, their servers can't be talked to: {}",
+            described.join(", ")
+        );
+    }
+    let strangers = untracked(state, running_sessions);
+    if !strangers.is_empty() && !opts.quiet {
+        println!(
+            "Started elsewhere, not tracked yet: {} — {} adopts one, or run `zellij-chooser adopt`",
+            strangers.join(", "),
+            config.keys.adopt
+        );
+    }
+    // Read once (and again once the sessions change); the picker keeps the
+    // ages current from these
+    let read_last_used = |state: &State, sessions: &[String]| -> HashMap<String, u64> {
+        sessions
+            .iter()
+            .filter_map(|s| Some((s.clone(), idle::last_used(socket_dir, s, state)?)))
+            .collect()
+    };
+    let mut last_used = read_last_used(state, &everything);
+    let mut sort = opts.sort.or(state.sort);
+    let mut group_by = opts.group_by.or(state.group_by).unwrap_or_default();
+    let mut first_run = true;
+    loop {
+        let ranker = Ranker::new(&matcher, &config.ranking, state, clock::now())
+            .pin_order(pinned.clone())
+            .presorted(sort.is_some());
+        let arrange =
+            |sessions| listing::arrange(sessions, opts.filter.as_ref(), sort, socket_dir, state);
+        let shown_sessions = arrange(running_sessions.clone());
+        let dead_sessions = arrange(offered_dead_sessions(opts, running_sessions));
+        let options = PickerOptions {
+            show_banner: first_run && !opts.quiet,
+            hide_help: config.prompt.hide_help,
+            prompt: config.prompt.format.clone(),
+            summary: Some(summary(
+                zellij,
+                opts,
+                state,
+                running_sessions,
+                &dead_sessions,
+            )),
+            theme: theme(opts, config),
+            explain_ranking: opts.explain_ranking,
+            initial_query: opts.query.clone().filter(|_| first_run).or_else(|| {
+                state
+                    .queries
+                    .last()
+                    .filter(|_| first_run && opts.filter_last)
+                    .cloned()
+            }),
+            accept_single_match: first_run && opts.query.is_some(),
+            history_file: state::state_dir()
+                .map(|dir| dir.join("history"))
+                .filter(|_| !dryrun::is_on()),
+            history: state.queries.clone(),
+            auto_preview: !opts.no_preview,
+            keys: config.keys.clone(),
+            ctrl_c: config.ctrl_c,
+            attached: running_sessions
+                .iter()
+                .filter(|s| backend.is_attached(s))
+                .cloned()
+                .collect(),
+            last_used: last_used.clone(),
+            groups: config.groups.clone(),
+            aliases: config.aliases.clone(),
+            sort,
+            group_by,
+            group_labels: listing::group_labels(
+                group_by,
+                &shown_sessions,
+                &dead_sessions,
+                &config.groups,
+                socket_dir,
+            ),
+            watch: Some(socket_dir.to_path_buf()),
+        };
+        first_run = false;
+        let preview = |session: &str| {
+            let layout = if dead_sessions.iter().any(|d| d == session) {
+                sessions::dead_session_layout(session).ok()?
+            } else {
+                match zellij.dump_layout(session) {
+                    Ok(layout) => layout,
+                    Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+                        return Some(format!("No preview: {}", err));
+                    }
+                    Err(_) => return None,
+                }
+            };
+            Some(preview::render(session, &preview::summarize(&layout)))
+        };
+        let details = |session: &str| {
+            let snapshot = procfs::Snapshot::take();
+            let server = snapshot.server_pid(&socket_dir.join(session));
+            let usage = server.map(|pid| snapshot.usage(pid));
+            let priority = server.and_then(procfs::describe);
+            Some(details::render(
+                session,
+                running_sessions.iter().any(|s| s == session),
+                sessions::created_at(socket_dir, session),
+                state.sessions.get(session),
+                usage,
+                priority.as_deref(),
+                clock::now(),
+            ))
+        };
+        match interactive_select(
+            &shown_sessions,
+            &dead_sessions,
+            &ranker,
+            options,
+            &preview,
+            &details,
+        ) {
+            Ok(Outcome::Attach(selection)) => return Ok(selection),
+            Ok(Outcome::Purge(name)) => {
+                if let Err(err) = purge_session(&name) {
+                    println!("{}", err);
+                }
+                bell::alert(config.bell.purged, &format!("{} is purged", name));
+            }
+            Ok(Outcome::Kill(name)) => {
+                if let Err(err) = backend.kill(&name) {
+                    println!("Couldn't kill \"{}\": {}", name, err);
+                }
+                *running_sessions = backend.list().unwrap_or_default();
+            }
+            Ok(Outcome::Clone { from, to }) => {
+                match clone_session(zellij, backend, config, state, running_sessions, &from, to) {
+                    Ok(name) => println!("Started \"{}\", a clone of \"{}\"", name, from),
+                    Err(err) => println!("{}", err),
+                }
+                *running_sessions = backend.list().unwrap_or_default();
+            }
+            Ok(Outcome::Refresh) => {
+                *running_sessions = backend.list().unwrap_or_default();
+                let everything: Vec<String> = running_sessions
+                    .iter()
+                    .cloned()
+                    .chain(offered_dead_sessions(opts, running_sessions))
+                    .collect();
+                last_used = read_last_used(state, &everything);
+            }
+            Ok(Outcome::Adopt {
+                session,
+                tags,
+                sets,
+            }) => {
+                adopt(socket_dir, state, &session, &tags, &sets);
+                save_state(state);
+            }
+            Ok(Outcome::Rename { from, to }) => {
+                if let Err(err) = rename_session(zellij, state, running_sessions, &from, &to) {
+                    println!("{}", err);
+                }
+                *running_sessions = backend.list().unwrap_or_default();
+            }
+            Ok(Outcome::Arrange {
+                sort: new_sort,
+                group_by: new_group_by,
+                remember,
+            }) => {
+                (sort, group_by) = (new_sort, new_group_by);
+                if remember {
+                    state.sort = sort;
+                    state.group_by = Some(group_by);
+                    save_state(state);
+                }
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+// Takes one end of what the picker would list, or without --first or --last,
+// prints the list for whatever script ran us and gives up
+fn pick_without_prompting(
+    socket_dir: &Path,
+    opts: &CliArgs,
+    config: &Config,
+    state: &State,
+    running_sessions: &[String],
+    matcher: &Matcher,
+    pinned: Vec<String>,
+) -> Result<Selection, Failure> {
+    let ranker = Ranker::new(matcher, &config.ranking, state, clock::now())
+        .pin_order(pinned)
+        .presorted(opts.sort.is_some());
+    let arrange =
+        |sessions| listing::arrange(sessions, opts.filter.as_ref(), opts.sort, socket_dir, state);
+    let shown_sessions = arrange(running_sessions.to_vec());
+    let dead_sessions = arrange(offered_dead_sessions(opts, running_sessions));
+    let query = opts.query.as_deref().unwrap_or_default();
+    let ranked = rank_all(&ranker, query, &shown_sessions, &dead_sessions);
+    let picked = if opts.first {
+        ranked.first()
+    } else if opts.last {
+        ranked.last()
+    } else {
+        for session in &ranked {
+            println!("{}", session.name);
+        }
+        return Err(Failure::NoTerminal(
+            "Not prompting without a terminal; name a session, or pass --first or --last"
+                .to_string(),
+        ));
+    };
+    match picked {
+        Some(session) => Ok(Selection {
+            name: session.name.to_string(),
+            detach_others: false,
+            queries: Vec::new(),
+            from_blueprint: false,
+        }),
+        None => Err(Failure::NotFound("There's no session to pick".to_string())),
+    }
 }
 
-fn exit_zellij_not_found() -> ! {
-    println!("Looks like zellij isn't available. Exiting.");
-    std::process::exit(-1);
+// Before anything's looked at, let alone renamed, in the state or in zellij
+fn renaming_supported(zellij: &Zellij) -> Result<(), Failure> {
+    zellij
+        .require(Feature::RenameSession)
+        .map_err(|err| Failure::from_zellij(err, "Can't rename sessions".to_string()))
+}
+
+fn rename_session(
+    zellij: &Zellij,
+    state: &mut State,
+    running_sessions: &[String],
+    from: &str,
+    to: &str,
+) -> Result<(), Failure> {
+    renaming_supported(zellij)?;
+    if !running_sessions.iter().any(|s| s == from) {
+        return Err(Failure::NotFound(format!(
+            "There's no running session called \"{}\"",
+            from
+        )));
+    }
+    if running_sessions.iter().any(|s| s == to) {
+        return Err(Failure::Other(format!(
+            "There's already a session called \"{}\"",
+            to
+        )));
+    }
+    zellij
+        .rename_session(from, to)
+        .map_err(|err| Failure::from_zellij(err, format!("Couldn't rename \"{}\"", from)))?;
+    state.rename_session(from, to);
+    save_state(state);
+    Ok(())
+}
+
+// Running sessions there's nothing about in the state: started outside the
+// chooser, and never attached to through it
+fn untracked(state: &State, running_sessions: &[String]) -> Vec<String> {
+    running_sessions
+        .iter()
+        .filter(|session| !state.sessions.contains_key(*session))
+        .cloned()
+        .collect()
+}
+
+// Tracks `session` from now on. Its cwd is a guess: wherever its server was
+// started, which is usually where whoever started it was.
+fn adopt(socket_dir: &Path, state: &mut State, session: &str, tags: &[String], sets: &[String]) {
+    let cwd = procfs::Snapshot::take()
+        .server_pid(&socket_dir.join(session))
+        .and_then(procfs::cwd);
+    match &cwd {
+        Some(cwd) => println!("Adopted {} (in {})", session, cwd.display()),
+        None => println!("Adopted {}", session),
+    }
+    state.adopt(session, cwd, tags, sets);
+}
+
+// Starts a copy of `from` laid out like it is now, or like it was when it
+// exited, from the same template and with the same tags. Without a name,
+// the copy is the first of `from-2`, `from-3`, ... that's free.
+fn clone_session(
+    zellij: &Zellij,
+    backend: &dyn SessionBackend,
+    config: &Config,
+    state: &mut State,
+    running_sessions: &[String],
+    from: &str,
+    to: Option<String>,
+) -> Result<String, Failure> {
+    let dead_sessions = get_dead_sessions(running_sessions);
+    let taken = |name: &str| {
+        running_sessions
+            .iter()
+            .chain(&dead_sessions)
+            .any(|s| s == name)
+    };
+    let to = match to {
+        Some(to) if taken(&to) => {
+            return Err(Failure::Other(format!(
+                "There's already a session called \"{}\"",
+                to
+            )))
+        }
+        Some(to) => to,
+        None => (2..)
+            .map(|n| format!("{}-{}", from, n))
+            .find(|name| !taken(name))
+            .unwrap_or_default(),
+    };
+    let layout = if running_sessions.iter().any(|s| s == from) {
+        zellij.dump_layout(from)
+    } else if dead_sessions.iter().any(|s| s == from) {
+        sessions::dead_session_layout(from)
+    } else {
+        return Err(Failure::NotFound(format!(
+            "There's no session called \"{}\"",
+            from
+        )));
+    }
+    .map_err(|err| {
+        Failure::from_zellij(err, format!("Couldn't read the layout of \"{}\"", from))
+    })?;
+    let record = state.sessions.get(from).cloned().unwrap_or_default();
+    let mut template = match &record.template {
+        Some(name) => resolve_template(config, Some(name)).unwrap_or_default(),
+        None => Template::default(),
+    };
+    let path = write_temp_layout(&layout).map_err(|err| err.to_string())?;
+    template.layout = Some(path.to_string_lossy().into_owned());
+    let spawned = backend.create(&to, &template);
+    let _ = fs::remove_file(&path);
+    spawned
+        .map_err(|err| Failure::from_zellij(err, format!("Couldn't create session \"{}\"", to)))?;
+    let copy = state.sessions.entry(to.clone()).or_default();
+    copy.tags = record.tags;
+    copy.cwd = record.cwd;
+    copy.template = record.template;
+    save_state(state);
+    Ok(to)
+}
+
+// Lists the sessions unused for `days`, or with `interactive`, asks about
+// each in turn whether it should go
+fn prune(
+    backend: &dyn SessionBackend,
+    socket_dir: &Path,
+    state: &State,
+    running_sessions: &[String],
+    days: u64,
+    interactive: bool,
+) -> Result<(), Failure> {
+    let dead_sessions = get_dead_sessions(running_sessions);
+    let everything: Vec<String> = running_sessions
+        .iter()
+        .chain(&dead_sessions)
+        .cloned()
+        .collect();
+    let now = clock::now();
+    let idle = idle::idle_sessions(&everything, socket_dir, state, days, now);
+    if idle.is_empty() {
+        println!("No session has been idle for {} days", days);
+        return Ok(());
+    }
+    for (session, used) in idle {
+        let dead = dead_sessions.contains(&session);
+        let status = if dead { "exited" } else { "running" };
+        let summary = format!(
+            "{} ({}, last used {})",
+            session,
+            status,
+            details::ago(used, now)
+        );
+        if !interactive {
+            println!("{}", summary);
+            continue;
+        }
+        let verb = if dead { "Purge" } else { "Kill" };
+        print!("{} {}? [y/N/q] ", verb, summary);
+        let _ = io::stdout().flush();
+        let mut answer = String::new();
+        if io::stdin()
+            .read_line(&mut answer)
+            .map_err(|err| err.to_string())?
+            == 0
+        {
+            return Err(Failure::Aborted);
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "q" => return Ok(()),
+            "y" if dead => purge_session(&session)?,
+            "y" => backend.kill(&session).map_err(|err| {
+                Failure::from_zellij(err, format!("Couldn't kill \"{}\"", session))
+            })?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn stats(
+    opts: &CliArgs,
+    state: &State,
+    running_sessions: &[String],
+    json: bool,
+) -> Result<(), Failure> {
+    // Running sessions never attached to count too, they're the ones
+    // nobody uses
+    let mut names: Vec<&String> = state.sessions.keys().chain(running_sessions).collect();
+    names.sort_unstable();
+    names.dedup();
+    names.retain(|name| opts.filter.as_ref().is_none_or(|f| f.matches(name)));
+    let record = |name: &str| state.sessions.get(name).cloned().unwrap_or_default();
+    names.sort_by_key(|name| {
+        let record = record(name);
+        std::cmp::Reverse((record.attached_secs, record.times_attached()))
+    });
+    if json {
+        let stats: Vec<serde_json::Value> = names
+            .iter()
+            .map(|name| {
+                let record = record(name);
+                serde_json::json!({
+                    "name": name,
+                    "attaches": record.times_attached(),
+                    "attached_secs": record.attached_secs,
+                    "last_attached": record.attaches.last(),
+                })
+            })
+            .collect();
+        let json = serde_json::to_string_pretty(&stats)
+            .map_err(|err| Failure::Other(format!("Couldn't print the stats: {}", err)))?;
+        println!("{}", json);
+        return Ok(());
+    }
+    let width = names.iter().map(|name| name.chars().count()).max();
+    let now = clock::now();
+    for name in names {
+        let record = record(name);
+        let last = match record.attaches.last() {
+            Some(at) => format!("last {}", details::ago(*at, now)),
+            None => "never".to_string(),
+        };
+        println!(
+            "{:width$}  {:>6} attach(es)  {:>8} attached  {}",
+            name,
+            locale::current().count(record.times_attached()),
+            details::duration(record.attached_secs),
+            last,
+            width = width.unwrap_or(0)
+        );
+    }
+    Ok(())
+}
+
+fn purge_session(name: &str) -> Result<(), String> {
+    sessions::purge_dead_session(name)
+        .map_err(|err| format!("Couldn't purge \"{}\": {}", name, err))
+}
+
+fn expand_sets(state: &State, config: &Config, names: &[String]) -> Result<Vec<String>, Failure> {
+    let names: Vec<String> = names
+        .iter()
+        .map(|name| config.unalias(name).to_string())
+        .collect();
+    state
+        .expand_sets(&names, &config.groups)
+        .map_err(|set| Failure::NotFound(format!("There's no set or group called \"{}\"", set)))
+}
+
+fn group<'c>(config: &'c Config, name: &str) -> Result<&'c [String], Failure> {
+    config.groups.get(name).map(Vec::as_slice).ok_or_else(|| {
+        Failure::NotFound(format!(
+            "There's no group called \"{}\" in the config",
+            name
+        ))
+    })
+}
+
+fn manage_sets(
+    state: &mut State,
+    config: &Config,
+    opts: &CliArgs,
+    running_sessions: &[String],
+    action: SetAction,
+) -> Result<(), Failure> {
+    match action {
+        SetAction::Save { name, sessions } => {
+            let members =
+                match (&opts.filter, sessions.is_empty()) {
+                    (_, false) => expand_sets(state, config, &sessions)?,
+                    (Some(filter), true) => running_sessions
+                        .iter()
+                        .filter(|s| filter.matches(s))
+                        .cloned()
+                        .collect(),
+                    (None, true) => return Err(Failure::Other(
+                        "Name the sessions to save, or pass --filter to save the ones it matches"
+                            .to_string(),
+                    )),
+                };
+            if members.is_empty() {
+                return Err(Failure::NotFound("No running session matches".to_string()));
+            }
+            println!("@{}: {}", name, members.join(" "));
+            state.sets.insert(name, members);
+        }
+        SetAction::List => {
+            for (name, members) in &state.sets {
+                println!("@{}: {}", name, members.join(" "));
+            }
+            return Ok(());
+        }
+        SetAction::Delete { name } => {
+            if state.sets.remove(&name).is_none() {
+                return Err(Failure::NotFound(format!(
+                    "There's no set called \"{}\"",
+                    name
+                )));
+            }
+        }
+    }
+    save_state(state);
+    Ok(())
+}
+
+// Starts a zellij session for each tmux one, from a layout mirroring its
+// windows. Names already taken in zellij are skipped.
+fn import_tmux(
+    backend: &dyn SessionBackend,
+    running_sessions: &[String],
+    names: Vec<String>,
+    dry_run: bool,
+) -> Result<(), Failure> {
+    let names = if names.is_empty() {
+        tmux::session_names()
+            .map_err(|err| Failure::Other(format!("Couldn't list the tmux sessions: {}", err)))?
+    } else {
+        names
+    };
+    let mut all_ok = true;
+    for (idx, name) in names.into_iter().enumerate() {
+        if running_sessions.contains(&name) {
+            eprintln!(
+                "Skipping {}, there's already a zellij session by that name",
+                name
+            );
+            all_ok = false;
+            continue;
+        }
+        let session = match tmux::read_session(&name) {
+            Ok(session) => session,
+            Err(err) => {
+                eprintln!("Couldn't read the tmux session {}: {}", name, err);
+                all_ok = false;
+                continue;
+            }
+        };
+        let layout = tmux::to_layout(&session);
+        if dry_run {
+            println!("// {}\n{}", name, layout);
+            continue;
+        }
+        // tmux allows any name, `/` included, so it's left out of the path
+        let path = env::temp_dir().join(format!(
+            "zellij-chooser-{}-tmux-{}.kdl",
+            std::process::id(),
+            idx
+        ));
+        let template = Template {
+            layout: Some(path.to_string_lossy().into_owned()),
+            ..Template::default()
+        };
+        let spawned = sessions::check_name(&name)
+            .and_then(|_| fs::write(&path, layout))
+            .and_then(|_| backend.create(&name, &template));
+        let _ = fs::remove_file(&path);
+        match spawned {
+            Ok(()) => println!("Imported {} ({} tab(s))", name, session.windows.len()),
+            Err(err) => {
+                eprintln!("Couldn't import {}: {}", name, err);
+                all_ok = false;
+            }
+        }
+    }
+    if !all_ok {
+        return Err(Failure::Other(
+            "Not every tmux session could be imported".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Carries out a batch of renames, printing a table of what happened to each
+// (or with `dry_run`, what would). False if any of them didn't go through.
+fn rename_many(
+    zellij: &Zellij,
+    state: &mut State,
+    running_sessions: &[String],
+    plan: &[(String, String)],
+    dry_run: bool,
+) -> bool {
+    let width = |column: fn(&(String, String)) -> &String| {
+        plan.iter()
+            .map(|row| column(row).chars().count())
+            .max()
+            .unwrap_or_default()
+    };
+    let (from_width, to_width) = (width(|row| &row.0), width(|row| &row.1));
+    let mut all_ok = true;
+    for (from, to) in plan {
+        let clashes = plan.iter().filter(|(_, other)| other == to).count() > 1;
+        let outcome = if to.trim().is_empty() || to.find(char::is_whitespace).is_some() {
+            Err("not a valid name".to_string())
+        } else if clashes {
+            Err("more than one session would get this name".to_string())
+        } else if running_sessions.contains(to) {
+            Err("there's already a session with this name".to_string())
+        } else if dry_run {
+            Ok("would rename")
+        } else {
+            rename_session(zellij, state, running_sessions, from, to)
+                .map(|_| "renamed")
+                .map_err(|err| err.to_string())
+        };
+        let outcome = match outcome {
+            Ok(done) => done.to_string(),
+            Err(err) => {
+                all_ok = false;
+                format!("skipped: {}", err)
+            }
+        };
+        println!(
+            "{:from_width$}  ->  {:to_width$}  {}",
+            from,
+            to,
+            outcome,
+            from_width = from_width,
+            to_width = to_width
+        );
+    }
+    all_ok
+}
+
+// Replaces us with the user's shell, telling it what was picked, so
+// whatever wraps the chooser can do the attaching itself
+fn exec_shell_with_selection(session: &str) -> io::Error {
+    let shell = env::var_os("SHELL").unwrap_or_else(|| "/bin/sh".into());
+    Command::new(shell)
+        .env("ZELLIJ_CHOOSER_SELECTION", session)
+        .exec()
+}
+
+// Looks up a template by name, falling back to the configured default one
+// (or an empty template) when no name is given
+fn resolve_template(config: &Config, name: Option<&str>) -> Result<Template, Failure> {
+    let name = match name.or(config.default_template.as_deref()) {
+        Some(name) => name,
+        None => return Ok(Template::default()),
+    };
+    let template = config.templates.get(name).ok_or_else(|| {
+        Failure::NotFound(format!(
+            "There's no template called \"{}\" in the config",
+            name
+        ))
+    })?;
+    Ok(Template {
+        name: Some(name.to_string()),
+        ..template.clone()
+    })
+}
+
+// Losing the state isn't worth failing over, but it's worth a mention
+fn save_state(state: &State) {
+    if dryrun::is_on() {
+        log::debug!("Not saving the state, it's a dry run");
+        return;
+    }
+    if let Err(err) = state.save() {
+        eprintln!("Couldn't save the chooser's state: {}", err);
+    }
+}
+
+fn zellij_not_found(zellij: &Zellij) -> Failure {
+    Failure::Zellij(format!(
+        "Looks like zellij isn't available (tried `{}`).",
+        zellij.bin.display()
+    ))
 }
 
 fn try_joining<T>(session_name: T::Item, sessions: T) -> io::Result<()>
@@ -76,98 +1750,562 @@ where
     }
 }
 
-// Retrieved from Zellij
-// https://github.com/zellij-org/zellij/blob/main/src/sessions.rs
-fn get_sessions() -> Result<Vec<String>, io::ErrorKind> {
-    match fs::read_dir(&*zellij_utils::consts::ZELLIJ_SOCK_DIR) {
-        Ok(files) => {
-            let mut sessions = Vec::new();
-            files.for_each(|file| {
-                let file = file.unwrap();
-                let file_name = file.file_name().into_string().unwrap();
-                if file.file_type().unwrap().is_socket() && assert_socket(&file_name) {
-                    sessions.push(file_name);
-                }
-            });
-            Ok(sessions)
+// What zellij sets in the environment of the panes it runs
+const ZELLIJ_PANE_VARS: [&str; 2] = ["ZELLIJ", "ZELLIJ_SESSION_NAME"];
+
+// Attaching from inside a zellij pane would nest one session in another.
+// Only what zellij sets in its panes counts: our own ZELLIJ_CHOOSER_* and
+// ZELLIJ_SOCKET_DIR are fine to have around.
+fn refuse_nesting() -> Result<(), Failure> {
+    if env::vars_os().any(|(k, _)| ZELLIJ_PANE_VARS.contains(&&*k.to_string_lossy())) {
+        return Err(Failure::Other(
+            "Already inside a zellij session; detach first, or use --print".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// How often `list --watch` probes the sockets, on top of whatever inotify
+// reports
+const WATCH_PROBE_EVERY: Duration = Duration::from_secs(2);
+
+// How long probing the sockets has to take before `bell.probes_done` rings:
+// any quicker and nobody's had time to look away
+const SLOW_PROBING: Duration = Duration::from_secs(2);
+
+// How long a freshly spawned server gets to put its socket in place,
+// unless the config says otherwise
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+// How long the client that started a session on an older zellij gets to
+// leave once it's been detached
+const HEADLESS_EXIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Starts the session's server in the background without attaching to it,
+// optionally with a layout, and only reports success once its socket
+// actually answers. A zellij without `attach --create-background` gets a
+// client on a terminal of its own instead, detached again once the server
+// is up.
+fn spawn(
+    zellij: &Zellij,
+    socket_dir: &Path,
+    config: &Config,
+    session: &str,
+    template: &Template,
+) -> io::Result<()> {
+    let wrapper = launcher::wrapper(template);
+    let mut cmd = zellij.command_via(&wrapper);
+    // So its shells know where to find what's refreshed on attach
+    if !launcher::refreshed_env(config, Some(template)).is_empty() {
+        if let Some(file) = launcher::env_file(session) {
+            cmd.env("ZELLIJ_CHOOSER_ENV_FILE", file);
+        }
+    }
+    launcher::environment(template, &mut cmd);
+    // The server's directory is where its panes start
+    if let Some(cwd) = &template.cwd {
+        let cwd = launcher::expand_home(cwd);
+        if !cwd.is_dir() {
+            return Err(io::Error::other(format!(
+                "{} isn't a directory",
+                cwd.display()
+            )));
+        }
+        cmd.current_dir(cwd);
+    }
+    let background = zellij.supports(Feature::CreateBackground);
+    let create = if background {
+        "--create-background"
+    } else {
+        "--create"
+    };
+    cmd.arg("attach").arg(create).arg(session);
+    if let Some(layout) = &template.layout {
+        cmd.arg("options").arg("--default-layout").arg(layout);
+    }
+    if dryrun::pretend(format_args!("start {} by running {:?}", session, cmd)) {
+        hooks::run(
+            &hooks::applying(config, Some(template)),
+            hooks::Event::Create,
+            session,
+        );
+        return Ok(());
+    }
+    log::info!("Spawning {:?}", session);
+    log::debug!("Running {:?}", cmd);
+    let wrapper_missing = |err: io::Error| match err.kind() {
+        io::ErrorKind::NotFound if !wrapper.is_empty() => io::Error::other(format!(
+            "couldn't run `{}`, which the template's limits or priorities need",
+            wrapper[0]
+        )),
+        _ => err,
+    };
+    let mut client = None;
+    if background {
+        let status = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .status()
+            .map_err(wrapper_missing)?;
+        if !status.success() {
+            return Err(io::Error::other(format!("zellij exited with {}", status)));
         }
-        Err(err) if io::ErrorKind::NotFound != err.kind() => Err(err.kind()),
-        Err(_) => Ok(Vec::with_capacity(0)),
+    } else {
+        client = Some(tty::spawn_headless(&mut cmd).map_err(wrapper_missing)?);
     }
+    let waiting = format!("Starting {}...", session);
+    let up = symbols::spin(&waiting, || {
+        let timeout = config
+            .spawn_timeout
+            .map_or(SPAWN_TIMEOUT, Duration::from_secs);
+        wait_for_socket(socket_dir, session, timeout).map_err(|err| match err.kind() {
+            io::ErrorKind::TimedOut => io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "its server didn't come up within {}s (if it's just slow, raise \
+                     `spawn_timeout` in the config; --debug shows what zellij was run with)",
+                    timeout.as_secs()
+                ),
+            ),
+            _ => err,
+        })
+    });
+    if let Some(client) = client {
+        // Killing it leaves the server be, if it came up at all
+        let detached = up.is_ok()
+            && sessions::detach_other_clients(socket_dir, session)
+                .inspect_err(|err| log::warn!("Couldn't detach from {:?}: {}", session, err))
+                .is_ok();
+        leave_headless(client, detached);
+    }
+    up?;
+    bell::alert(config.bell.created, &format!("{} is up", session));
+    hooks::run(
+        &hooks::applying(config, Some(template)),
+        hooks::Event::Create,
+        session,
+    );
+    Ok(())
 }
 
-fn assert_socket(name: &str) -> bool {
-    let path = &*ZELLIJ_SOCK_DIR.join(name);
-    match LocalSocketStream::connect(path) {
-        Ok(stream) => {
-            let mut sender = IpcSenderWithContext::new(stream);
-            let _ = sender.send(ClientToServerMsg::ConnStatus);
-            let mut receiver: IpcReceiverWithContext<ServerToClientMsg> = sender.get_receiver();
-            match receiver.recv() {
-                Some((ServerToClientMsg::Connected, _)) => true,
-                None | Some((_, _)) => false,
-            }
+// Waits for the client `spawn` started a session with to leave, once
+// `detached`, or makes it
+fn leave_headless(mut client: std::process::Child, detached: bool) {
+    let deadline = Instant::now() + HEADLESS_EXIT_TIMEOUT;
+    while detached && Instant::now() < deadline {
+        if let Ok(Some(_)) = client.try_wait() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    log::debug!("The client that started the session didn't leave, killing it");
+    let _ = client.kill();
+    let _ = client.wait();
+}
+
+// zellij only takes layouts from files; whoever asked for this one removes
+// it once zellij has read it
+fn write_temp_layout(layout: &str) -> io::Result<PathBuf> {
+    let path = env::temp_dir().join(format!(
+        "zellij-chooser-{}-{:08x}.kdl",
+        std::process::id(),
+        clock::random_below(1 << 32)
+    ));
+    fs::write(&path, layout)?;
+    Ok(path)
+}
+
+// Which of a session's tabs is meant: a name, a number as shown in the
+// preview, or if neither was given, whatever the user picks from the preview
+fn choose_tab(session: &str, tabs: &[TabSummary], wanted: Option<&str>) -> Result<usize, Failure> {
+    let wanted = match wanted {
+        Some(wanted) => wanted.to_string(),
+        None => {
+            println!("{}", preview::render(session, tabs));
+            print!("Which tab (name or number)? ");
+            let _ = io::stdout().flush();
+            let mut answer = String::new();
+            io::stdin()
+                .read_line(&mut answer)
+                .map_err(|err| err.to_string())?;
+            answer.trim().to_string()
         }
-        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
-            drop(fs::remove_file(path));
-            false
+    };
+    tabs.iter()
+        .position(|tab| tab.name == wanted)
+        .or_else(|| {
+            wanted
+                .parse::<usize>()
+                .ok()
+                .filter(|n| (1..=tabs.len()).contains(n))
+                .map(|n| n - 1)
+        })
+        .ok_or_else(|| Failure::NotFound(format!("\"{}\" has no tab \"{}\"", session, wanted)))
+}
+
+// Where the session's tab `wanted` (a name or a number) is, counting from 1
+fn tab_position(zellij: &Zellij, session: &str, wanted: &str) -> Result<usize, Failure> {
+    let layout = zellij.dump_layout(session).map_err(|err| {
+        Failure::from_zellij(err, format!("Couldn't read the tabs of \"{}\"", session))
+    })?;
+    let tabs = preview::summarize(&layout);
+    Ok(choose_tab(session, &tabs, Some(wanted))? + 1)
+}
+
+// Asks which of the session's tabs to attach to, where a plain Enter
+// leaves it on the one it's on. Sessions with a single tab, and ones
+// there's no layout to be had from (like exited ones, which attaching
+// brings back), aren't asked about.
+fn pick_tab_to_attach(zellij: &Zellij, session: &str) -> Result<Option<usize>, Failure> {
+    let tabs = match zellij.dump_layout(session) {
+        Ok(layout) => preview::summarize(&layout),
+        Err(err) => {
+            log::info!("Not asking for a tab, no layout of {:?}: {}", session, err);
+            return Ok(None);
         }
-        Err(_) => false,
+    };
+    if tabs.len() < 2 {
+        return Ok(None);
     }
+    println!("{}", preview::render(session, &tabs));
+    print!("Which tab (name or number, Enter for the one it's on)? ");
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|err| err.to_string())?;
+    if answer.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(choose_tab(session, &tabs, Some(answer.trim()))? + 1))
 }
 
-fn spawn<T: AsRef<OsStr>>(session: T) -> io::Result<()> {
+// Recreates one of `from`'s tabs in `to`, then closes the original unless
+// it's a copy. Only the layout travels, so the tab's commands start over.
+fn move_tab(
+    zellij: &Zellij,
+    running_sessions: &[String],
+    from: &str,
+    to: &str,
+    tab: Option<&str>,
+    copy: bool,
+) -> Result<(), Failure> {
+    for session in [from, to] {
+        if !running_sessions.iter().any(|s| s == session) {
+            return Err(Failure::NotFound(format!(
+                "There's no running session called \"{}\"",
+                session
+            )));
+        }
+    }
+    if from == to {
+        return Err(Failure::Other("Both sessions are the same".to_string()));
+    }
+    let layout = zellij.dump_layout(from).map_err(|err| {
+        Failure::from_zellij(err, format!("Couldn't read the layout of \"{}\"", from))
+    })?;
+    let tabs = preview::summarize(&layout);
+    let index = choose_tab(from, &tabs, tab)?;
+    let name = &tabs[index].name;
+    if !copy && tabs.len() == 1 {
+        return Err(Failure::Other(format!(
+            "\"{}\" is the only tab in \"{}\", moving it would end the session; \
+             use --copy instead",
+            name, from
+        )));
+    }
+    let tab_layout = preview::extract_tab(&layout, index)
+        .ok_or_else(|| format!("Couldn't find \"{}\" in the layout of \"{}\"", name, from))?;
+    let path = write_temp_layout(&tab_layout).map_err(|err| err.to_string())?;
+    let opened = zellij.new_tab(to, &path, name);
+    let _ = fs::remove_file(&path);
+    opened.map_err(|err| format!("Couldn't open the tab in \"{}\": {}", to, err))?;
+    if !copy {
+        zellij
+            .close_tab(from, name)
+            .map_err(|err| format!("Copied, but couldn't close it in \"{}\": {}", from, err))?;
+    }
     Ok(())
 }
 
-#[allow(clippy::all)]
-fn connect<T: AsRef<OsStr>>(session: T) -> Result<std::process::Child, std::io::Error> {
-    // The tricky part here is that we don't want to occupy
-    // two entire processes, where one of them is a deadbeat parent
-    // So, my idea here is to fork into a daemon, but preserve all the
-    // relevant pipes
-    if let Ok(Fork::Child) = daemon(
-        /* nochdir: bool = */ false, /* noclose: bool = */ true,
-    ) {
-        // Opting to use `.spawn()` since it inherits the pipes
-        // Otherwise, `.output()` would create new ones and detach
-        Command::new("zellij").arg("attach").arg("-c").arg(session).spawn()
-    } else {
-        Err(std::io::Error::new(
-            io::ErrorKind::BrokenPipe,
-            "Broke the forked connection",
-        ))
+// Starts a new session laid out like one of `from`'s tabs, closing that tab
+// unless it's a copy
+#[allow(clippy::too_many_arguments)]
+fn break_tab(
+    zellij: &Zellij,
+    backend: &dyn SessionBackend,
+    running_sessions: &[String],
+    from: &str,
+    to: &str,
+    tab: Option<&str>,
+    copy: bool,
+) -> Result<(), Failure> {
+    if !running_sessions.iter().any(|s| s == from) {
+        return Err(Failure::NotFound(format!(
+            "There's no running session called \"{}\"",
+            from
+        )));
+    }
+    if running_sessions.iter().any(|s| s == to) {
+        return Err(Failure::Other(format!(
+            "There's already a session called \"{}\"",
+            to
+        )));
+    }
+    let layout = zellij.dump_layout(from).map_err(|err| {
+        Failure::from_zellij(err, format!("Couldn't read the layout of \"{}\"", from))
+    })?;
+    let tabs = preview::summarize(&layout);
+    let index = choose_tab(from, &tabs, tab)?;
+    let name = &tabs[index].name;
+    if !copy && tabs.len() == 1 {
+        return Err(Failure::Other(format!(
+            "\"{}\" is the only tab in \"{}\", breaking it out would end the session; \
+             use --copy instead",
+            name, from
+        )));
     }
+    let tab_layout = preview::extract_tab(&layout, index)
+        .ok_or_else(|| format!("Couldn't find \"{}\" in the layout of \"{}\"", name, from))?;
+    let path = write_temp_layout(&tab_layout).map_err(|err| err.to_string())?;
+    let template = Template {
+        layout: Some(path.to_string_lossy().into_owned()),
+        ..Template::default()
+    };
+    let spawned = backend.create(to, &template);
+    let _ = fs::remove_file(&path);
+    spawned
+        .map_err(|err| Failure::from_zellij(err, format!("Couldn't create session \"{}\"", to)))?;
+    if !copy {
+        zellij.close_tab(from, name).map_err(|err| {
+            format!(
+                "\"{}\" is up, but the tab couldn't be closed in \"{}\": {}",
+                to, from, err
+            )
+        })?;
+    }
+    println!("Started \"{}\" from tab \"{}\" of \"{}\"", to, name, from);
+    Ok(())
 }
 
-fn interactive_select<T>(sessions: T) -> Result<(), Box<dyn std::error::Error>>
-where
-    T: IntoIterator + std::marker::Copy,
-    T::Item: AsRef<str> + std::fmt::Display,
-{
-    println!("Create a new session by entering the name for it, or select one from these options:");
-
-    let mut repl = Editor::<()>::new()?;
+// Starts an exited session over under the same name, laid out like it was
+// but with plain shells where its commands ran. Its saved state has to go
+// first, or zellij would resurrect it rather than use the layout.
+fn recreate_from_blueprint(backend: &dyn SessionBackend, session: &str) -> io::Result<()> {
+    let layout = preview::strip_commands(&sessions::dead_session_layout(session)?);
+    let path = write_temp_layout(&layout)?;
+    sessions::purge_dead_session(session)?;
+    let template = Template {
+        layout: Some(path.to_string_lossy().into_owned()),
+        ..Template::default()
+    };
+    let result = backend.create(session, &template);
+    let _ = fs::remove_file(&path);
+    result
+}
 
-    ctrlc::set_handler(move || {
-        println!("\rEnter nil to drop to normal prompt");
-    })
-    .expect("Error setting Ctrl-C handler");
+// The picker may well have saved the state since this run loaded it, so
+// it's loaded afresh
+fn record_time_attached(session: &str, secs: u64) {
+    if let Err(err) = store::current().reopen() {
+        log::warn!("Couldn't reopen the state: {}", err);
+        return;
+    }
+    match State::load() {
+        Ok(mut state) => {
+            state.record_time_attached(session, secs);
+            save_state(&state);
+        }
+        Err(err) => log::warn!("Couldn't load the state to record time attached: {}", err),
+    }
+}
 
-    let stdin: String = loop {
-        for (id, session) in sessions.into_iter().enumerate() {
-            println!("({}) :: {}", &stringify!(id), &session);
+#[allow(clippy::all)]
+fn connect(
+    backend: &dyn SessionBackend,
+    config: &Config,
+    state: &State,
+    session: &str,
+    tab: Option<usize>,
+) -> Result<(), Failure> {
+    refuse_nesting()?;
+    let pending = signals::pending()
+        .map_err(|err| Failure::Other(format!("Couldn't set up signal handling: {}", err)))?;
+    let refreshed = launcher::refreshed_env(config, state.template_of(config, session));
+    if !refreshed.is_empty() {
+        if let Err(err) = launcher::refresh_env(session, &refreshed) {
+            eprintln!("Couldn't refresh the session's environment: {}", err);
         }
-        let feed = repl.readline(">>> ")?;
-        if feed.is_empty() {
-            continue;
+    }
+    let hooks = hooks::for_session(config, state, session);
+    hooks::run(&hooks, hooks::Event::Attach, session);
+    let run = config.run_on_attach.get(session).cloned().or_else(|| {
+        state
+            .template_of(config, session)
+            .and_then(|template| template.run_on_attach.clone())
+    });
+    let after = AfterAttach { tab, run };
+    log::info!("Attaching to {:?}", session);
+    // zellij is about to take over the terminal, so stderr is off limits
+    if let Some(path) = logging::log_file().filter(|_| log::log_enabled!(log::Level::Info)) {
+        log::info!("The rest of the log goes to {}", path.display());
+    }
+    if let Err(err) = logging::to_file() {
+        log::warn!("Couldn't open the log file: {}", err);
+    }
+    if config.terminal_title {
+        bell::set_title(session);
+    }
+    let started = Instant::now();
+    let outcome = backend.attach(session, &after, &pending);
+    if config.terminal_title && !matches!(outcome, Ok(handoff::Outcome::HandedOff)) {
+        bell::restore_title();
+    }
+    match outcome {
+        Ok(handoff::Outcome::Finished(status)) => {
+            log::info!("zellij attach exited: {:?}", status);
+            record_time_attached(session, started.elapsed().as_secs());
+            // The terminal is ours again
+            hooks::run(&hooks, hooks::Event::Detach, session);
+            Ok(())
         }
-        if let Some(_) = &feed.find(char::is_whitespace) {
-            continue;
+        Ok(handoff::Outcome::HandedOff) => Ok(()),
+        Ok(handoff::Outcome::Cancelled(phase)) => {
+            log::info!("Cancelled before attaching ({:?})", phase);
+            Err(Failure::Aborted)
         }
-        break feed.to_string();
-    };
-    spawn(&stdin)?;
+        Err(err) => {
+            log::error!("Couldn't attach: {}", err);
+            Err(Failure::from_zellij(
+                err,
+                format!("Couldn't attach to \"{}\"", session),
+            ))
+        }
+    }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::mock::MemoryBackend;
+    use clap::Parser;
+
+    fn selection(name: &str) -> Selection {
+        Selection {
+            name: name.to_string(),
+            detach_others: false,
+            queries: Vec::new(),
+            from_blueprint: false,
+        }
+    }
+
+    fn running(backend: &MemoryBackend) -> Vec<String> {
+        backend.list().unwrap()
+    }
+
+    // Lock files are per name, so tests running at once mustn't share one
+    fn unique(name: &str) -> String {
+        format!("{}-{}", name, std::process::id())
+    }
+
+    #[test]
+    fn a_running_selection_is_attached_to_as_it_is() {
+        let backend = MemoryBackend::with(&["work", "play"]);
+        let mut state = State::default();
+        let running = running(&backend);
+        let config = Config::default();
+        make_running(
+            &backend,
+            &config,
+            &mut state,
+            &running,
+            &selection("work"),
+            false,
+        )
+        .unwrap();
+        assert!(backend.calls.lock().unwrap().is_empty());
+        assert!(state.sessions.is_empty());
+    }
+
+    #[test]
+    fn a_selection_that_isnt_running_is_created_first() {
+        let backend = MemoryBackend::with(&["work"]);
+        let mut state = State::default();
+        let running = running(&backend);
+        let config = Config::default();
+        let fresh = unique("picked");
+        make_running(
+            &backend,
+            &config,
+            &mut state,
+            &running,
+            &selection(&fresh),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            *backend.calls.lock().unwrap(),
+            [format!("create {}", fresh)]
+        );
+        assert!(backend.probe(&fresh).is_some());
+        assert!(state.sessions.contains_key(&fresh));
+    }
+
+    #[test]
+    fn detaching_the_others_goes_through_the_backend() {
+        let backend = MemoryBackend {
+            attached: vec!["work".to_string()],
+            ..MemoryBackend::with(&["work"])
+        };
+        let mut state = State::default();
+        let running = running(&backend);
+        let config = Config::default();
+        assert!(backend.is_attached("work"));
+        let picked = Selection {
+            detach_others: true,
+            ..selection("work")
+        };
+        make_running(&backend, &config, &mut state, &running, &picked, false).unwrap();
+        make_running(
+            &backend,
+            &config,
+            &mut state,
+            &running,
+            &selection("work"),
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            *backend.calls.lock().unwrap(),
+            ["detach work", "detach work"]
+        );
+    }
+
+    #[test]
+    fn without_prompting_either_end_of_the_list_is_taken() {
+        let backend = MemoryBackend::with(&["play", "work"]);
+        let running = running(&backend);
+        let config = Config::default();
+        let state = State::default();
+        let matcher = Matcher::new(&config.matching);
+        let pick = |args: &[&str]| {
+            let opts = CliArgs::parse_from(["zellij-chooser"].iter().chain(args));
+            pick_without_prompting(
+                Path::new("/nonexistent"),
+                &opts,
+                &config,
+                &state,
+                &running,
+                &matcher,
+                Vec::new(),
+            )
+        };
+        assert_eq!(pick(&["--first", "--query", "pla"]).unwrap().name, "play");
+        assert_eq!(pick(&["--last", "--query", "wor"]).unwrap().name, "work");
+        assert!(matches!(
+            pick(&["--first", "--query", "zzz"]),
+            Err(Failure::NotFound(_))
+        ));
+        assert!(matches!(pick(&[]), Err(Failure::NoTerminal(_))));
+    }
 }
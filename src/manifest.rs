@@ -0,0 +1,14 @@
+use std::path::Path;
+use std::{fs, io};
+
+/// Reads a manifest: one session name per line, in the order they should
+/// be listed. Blank lines and `#` comments are skipped.
+pub fn load(path: &Path) -> io::Result<Vec<String>> {
+    let raw = fs::read_to_string(path)?;
+    Ok(raw
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
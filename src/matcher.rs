@@ -0,0 +1,241 @@
+use crate::config::{CaseMode, MatchingConfig};
+
+// Latin letters carrying diacritics, grouped by the plain letter they fold to.
+// This covers Latin-1 and Latin Extended-A, which is where session names
+// realistically come from; anything else is compared as-is.
+const FOLDS: &[(&str, char)] = &[
+    ("àáâãäåāăą", 'a'),
+    ("ÀÁÂÃÄÅĀĂĄ", 'A'),
+    ("çćĉċč", 'c'),
+    ("ÇĆĈĊČ", 'C'),
+    ("ďđ", 'd'),
+    ("ĎĐ", 'D'),
+    ("èéêëēĕėęě", 'e'),
+    ("ÈÉÊËĒĔĖĘĚ", 'E'),
+    ("ĝğġģ", 'g'),
+    ("ĜĞĠĢ", 'G'),
+    ("ĥħ", 'h'),
+    ("ĤĦ", 'H'),
+    ("ìíîïĩīĭįı", 'i'),
+    ("ÌÍÎÏĨĪĬĮİ", 'I'),
+    ("ĵ", 'j'),
+    ("Ĵ", 'J'),
+    ("ķ", 'k'),
+    ("Ķ", 'K'),
+    ("ĺļľŀł", 'l'),
+    ("ĹĻĽĿŁ", 'L'),
+    ("ñńņňŉ", 'n'),
+    ("ÑŃŅŇ", 'N'),
+    ("òóôõöøōŏő", 'o'),
+    ("ÒÓÔÕÖØŌŎŐ", 'O'),
+    ("ŕŗř", 'r'),
+    ("ŔŖŘ", 'R'),
+    ("śŝşš", 's'),
+    ("ŚŜŞŠ", 'S'),
+    ("ţťŧ", 't'),
+    ("ŢŤŦ", 'T'),
+    ("ùúûüũūŭůűų", 'u'),
+    ("ÙÚÛÜŨŪŬŮŰŲ", 'U'),
+    ("ŵ", 'w'),
+    ("Ŵ", 'W'),
+    ("ýÿŷ", 'y'),
+    ("ÝŸŶ", 'Y'),
+    ("źżž", 'z'),
+    ("ŹŻŽ", 'Z'),
+];
+
+fn fold_diacritic(c: char) -> char {
+    if c.is_ascii() {
+        return c;
+    }
+    FOLDS
+        .iter()
+        .find(|(accented, _)| accented.contains(c))
+        .map_or(c, |(_, plain)| *plain)
+}
+
+// Combining diacritical marks, i.e. what's left over from decomposed input
+fn is_combining_mark(c: char) -> bool {
+    ('\u{0300}'..='\u{036f}').contains(&c)
+}
+
+fn is_word_boundary(c: char) -> bool {
+    matches!(c, '-' | '_' | '.' | '/' | ' ' | ':')
+}
+
+/// Fuzzy subsequence matcher honoring the `[matching]` config section
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    case: CaseMode,
+    ignore_diacritics: bool,
+}
+
+impl Matcher {
+    pub fn new(config: &MatchingConfig) -> Self {
+        Self {
+            case: config.case,
+            ignore_diacritics: config.ignore_diacritics,
+        }
+    }
+
+    fn normalize(&self, text: &str, case_sensitive: bool) -> Vec<char> {
+        text.chars()
+            .filter(|c| !(self.ignore_diacritics && is_combining_mark(*c)))
+            .map(|c| {
+                if self.ignore_diacritics {
+                    fold_diacritic(c)
+                } else {
+                    c
+                }
+            })
+            .flat_map(|c| -> Vec<char> {
+                if case_sensitive {
+                    vec![c]
+                } else {
+                    c.to_lowercase().collect()
+                }
+            })
+            .collect()
+    }
+
+    fn case_sensitive(&self, query: &str) -> bool {
+        match self.case {
+            CaseMode::Sensitive => true,
+            CaseMode::Insensitive => false,
+            CaseMode::Smart => query.chars().any(char::is_uppercase),
+        }
+    }
+
+    /// Scores `candidate` against `query`, or `None` if it doesn't match.
+    /// Every query character has to show up in order; contiguous runs and
+    /// hits on word boundaries are worth more.
+    pub fn score(&self, query: &str, candidate: &str) -> Option<i64> {
+        let case_sensitive = self.case_sensitive(query);
+        let query = self.normalize(query, case_sensitive);
+        let candidate = self.normalize(candidate, case_sensitive);
+        if query.is_empty() {
+            return Some(0);
+        }
+
+        let mut score = 0_i64;
+        let mut wanted = query.iter().peekable();
+        let mut previous_hit: Option<usize> = None;
+        for (idx, c) in candidate.iter().enumerate() {
+            let Some(q) = wanted.peek() else { break };
+            if *q != c {
+                continue;
+            }
+            wanted.next();
+            score += 1;
+            if idx == 0 || is_word_boundary(candidate[idx - 1]) {
+                score += 8;
+            }
+            match previous_hit {
+                Some(prev) if prev + 1 == idx => score += 5,
+                Some(prev) => score -= (idx - prev - 1).min(3) as i64,
+                None => score -= idx.min(5) as i64,
+            }
+            previous_hit = Some(idx);
+        }
+        if wanted.peek().is_some() {
+            return None;
+        }
+        if query == candidate {
+            score += 100;
+        }
+        Some(score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(case: CaseMode, ignore_diacritics: bool) -> Matcher {
+        Matcher::new(&MatchingConfig {
+            case,
+            ignore_diacritics,
+        })
+    }
+
+    fn default() -> Matcher {
+        Matcher::new(&MatchingConfig::default())
+    }
+
+    #[test]
+    fn an_empty_query_matches_everything_equally() {
+        let matcher = default();
+        assert_eq!(matcher.score("", "api"), Some(0));
+        assert_eq!(matcher.score("", ""), Some(0));
+        assert_eq!(matcher.score("", "東京"), Some(0));
+    }
+
+    #[test]
+    fn every_query_character_has_to_show_up_in_order() {
+        let matcher = default();
+        assert!(matcher.score("api", "a-p-i").is_some());
+        assert_eq!(matcher.score("api", "pia"), None);
+        assert_eq!(matcher.score("apii", "api"), None);
+        assert_eq!(matcher.score("api", ""), None);
+    }
+
+    #[test]
+    fn better_matches_rank_higher() {
+        let matcher = default();
+        let ranked = ["api", "api-server", "my-api", "xapi", "axpxi"];
+        let scores: Vec<i64> = ranked
+            .iter()
+            .map(|candidate| matcher.score("api", candidate).unwrap())
+            .collect();
+        assert!(
+            scores.windows(2).all(|pair| pair[0] > pair[1]),
+            "{:?} should be in descending order",
+            scores
+        );
+    }
+
+    #[test]
+    fn an_exact_match_beats_any_prefix_match() {
+        let matcher = default();
+        let exact = matcher.score("api", "api").unwrap();
+        let prefix = matcher.score("api", "api-server").unwrap();
+        assert_eq!(exact, prefix + 100);
+        // Exact once case and diacritics are set aside
+        assert_eq!(matcher.score("api", "API"), Some(exact));
+        assert_eq!(matcher.score("cafe", "café"), matcher.score("cafe", "cafe"));
+    }
+
+    #[test]
+    fn case_follows_the_config() {
+        let smart = default();
+        assert!(smart.score("api", "API").is_some());
+        assert_eq!(smart.score("Api", "api"), None);
+        assert!(smart.score("Api", "Api-server").is_some());
+        assert_eq!(matcher(CaseMode::Sensitive, true).score("api", "API"), None);
+        assert!(matcher(CaseMode::Insensitive, true)
+            .score("API", "api")
+            .is_some());
+    }
+
+    #[test]
+    fn diacritics_fold_unless_told_not_to() {
+        let folding = default();
+        assert!(folding.score("cafe", "café").is_some());
+        assert!(folding.score("café", "cafe").is_some());
+        // Decomposed, as macOS file names come
+        assert!(folding.score("cafe", "cafe\u{301}").is_some());
+        assert!(folding.score("Zurich", "Zürich").is_some());
+        let strict = matcher(CaseMode::Smart, false);
+        assert_eq!(strict.score("cafe", "café"), None);
+        assert!(strict.score("café", "café").is_some());
+    }
+
+    #[test]
+    fn other_scripts_match_as_is() {
+        let matcher = default();
+        assert!(matcher.score("東京", "東京-dev").is_some());
+        assert_eq!(matcher.score("京東", "東京"), None);
+        assert!(matcher.score("αθ", "ΑΘΗΝΑ").is_some());
+        assert_eq!(matcher.score("Αθ", "αθηνα"), None);
+    }
+}
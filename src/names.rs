@@ -0,0 +1,42 @@
+use crate::clock;
+
+// Short, and easy to say and to type
+const ADJECTIVES: &[&str] = &[
+    "amber", "bold", "brave", "brisk", "calm", "clever", "cosmic", "crisp", "dapper", "eager",
+    "fancy", "fuzzy", "gentle", "glad", "golden", "happy", "hazy", "humble", "icy", "jolly",
+    "keen", "lively", "lucky", "mellow", "merry", "misty", "nimble", "noble", "odd", "plucky",
+    "polite", "proud", "quick", "quiet", "rapid", "rustic", "shiny", "silent", "sleepy", "snowy",
+    "steady", "sunny", "swift", "tidy", "tiny", "upbeat", "vivid", "warm", "wild", "witty",
+    "young", "zesty",
+];
+
+const NOUNS: &[&str] = &[
+    "acorn", "badger", "beacon", "bison", "brook", "cactus", "canyon", "cedar", "comet", "coral",
+    "crane", "delta", "dune", "ember", "falcon", "fern", "fjord", "gecko", "glacier", "harbor",
+    "heron", "island", "jaguar", "kettle", "lagoon", "lantern", "lemur", "maple", "meadow",
+    "nebula", "otter", "panda", "pebble", "pine", "quasar", "raven", "reef", "river", "saddle",
+    "salmon", "summit", "thistle", "tiger", "tundra", "valley", "violet", "walrus", "willow",
+    "yak", "zephyr",
+];
+
+fn pick<'a>(words: &[&'a str]) -> &'a str {
+    words[clock::random_below(words.len() as u64) as usize]
+}
+
+/// A two-word name like `brisk-otter` that isn't `taken` yet, for a session
+/// nobody named. There are a couple of thousand; once the tries run out, a
+/// number goes on the end.
+pub fn memorable<'a>(taken: impl IntoIterator<Item = &'a String> + Clone) -> String {
+    let free = |name: &str| !taken.clone().into_iter().any(|t| t == name);
+    for _ in 0..64 {
+        let name = format!("{}-{}", pick(ADJECTIVES), pick(NOUNS));
+        if free(&name) {
+            return name;
+        }
+    }
+    let base = format!("{}-{}", pick(ADJECTIVES), pick(NOUNS));
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|name| free(name))
+        .unwrap_or(base)
+}
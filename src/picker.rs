@@ -0,0 +1,841 @@
+use crate::clock;
+use crate::config::{CtrlC, Key, KeysConfig};
+use crate::details;
+use crate::listing::{GroupBy, SortKey};
+use crate::names;
+use crate::ranking::{Ranked, Ranker};
+use crate::refresh::{self, Screen, Watch};
+use crate::symbols;
+use crate::theme::{self, Rgb, Theme};
+use crate::watch::Watcher;
+use clap::ValueEnum;
+use rustyline::{
+    // validate::{ValidationContext, ValidationResult, Validator},
+    error::ReadlineError,
+    Cmd,
+    ConditionalEventHandler,
+    Editor,
+    Event,
+    EventContext,
+    EventHandler,
+    ExternalPrinter,
+    KeyCode,
+    KeyEvent,
+    Modifiers,
+    RepeatCount,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// How often the sockets are probed while the prompt waits, on top of
+// whatever inotify reports
+const PROBE_EVERY: Duration = Duration::from_secs(5);
+
+/// What the user settled on in the interactive prompt
+pub struct Selection {
+    pub name: String,
+    pub detach_others: bool,
+    /// Filter queries typed along the way, worth remembering for next time
+    pub queries: Vec<String>,
+    /// For an exited session: start it over from its layout, without
+    /// rerunning its commands, instead of resurrecting it as it was
+    pub from_blueprint: bool,
+}
+
+/// Why the picker returned
+pub enum Outcome {
+    /// Attach to this session, creating it if need be
+    Attach(Selection),
+    /// Rename a running session; the picker is expected to be reopened after
+    Rename { from: String, to: String },
+    /// Start tracking a session started elsewhere, tagged and in the sets
+    /// given, then reopen the picker
+    Adopt {
+        session: String,
+        tags: Vec<String>,
+        sets: Vec<String>,
+    },
+    /// Start a copy of a session, named `to` or whatever's free, then
+    /// reopen the picker
+    Clone { from: String, to: Option<String> },
+    /// Delete a resurrectable session's saved state, then reopen the picker
+    Purge(String),
+    /// Kill a running session, then reopen the picker
+    Kill(String),
+    /// Sessions started or went away while the picker was open; reopen it
+    /// with them as they are now
+    Refresh,
+    /// Reopen the picker sorted and grouped like this; `remember` to keep
+    /// it for next time
+    Arrange {
+        sort: Option<SortKey>,
+        group_by: GroupBy,
+        remember: bool,
+    },
+}
+
+/// Knobs for a single run of the picker
+#[derive(Debug, Default)]
+pub struct PickerOptions {
+    pub show_banner: bool,
+    /// Leave what the keys do out of the banner
+    pub hide_help: bool,
+    /// What's written before the cursor, with `{count}`, `{running}`,
+    /// `{dead}` and `{query}` filled in; `>>> ` if not given
+    pub prompt: Option<String>,
+    /// Heads the banner: what zellij, how many sessions, when last used
+    pub summary: Option<String>,
+    /// `None` when output should stay monochrome
+    pub theme: Option<Theme>,
+    pub explain_ranking: bool,
+    /// Start out narrowed down to this query
+    pub initial_query: Option<String>,
+    /// Don't even prompt when the initial query leaves a single candidate
+    pub accept_single_match: bool,
+    /// Where lines typed into the prompt are kept across runs, for Up-arrow
+    /// and Ctrl-R search on an empty line
+    pub history_file: Option<PathBuf>,
+    /// Earlier queries, oldest first, to start the history off with when
+    /// there's no history file yet
+    pub history: Vec<String>,
+    /// Preview the best match whenever the list is narrowed down
+    pub auto_preview: bool,
+    pub keys: KeysConfig,
+    pub ctrl_c: CtrlC,
+    /// Running sessions someone is attached to
+    pub attached: Vec<String>,
+    /// When each session was last used, for the "3m ago" column
+    pub last_used: HashMap<String, u64>,
+    /// The config's groups, shown next to their sessions
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// The config's aliases, shown next to their sessions and taken for
+    /// them when typed
+    pub aliases: BTreeMap<String, String>,
+    /// How the list is sorted (`None` for by rank) and grouped, for the
+    /// sort menu to start from
+    pub sort: Option<SortKey>,
+    pub group_by: GroupBy,
+    /// The heading each session is listed under, when grouped
+    pub group_labels: HashMap<String, String>,
+    /// Where to look out for sessions starting and going away while the
+    /// prompt waits
+    pub watch: Option<PathBuf>,
+}
+
+/// Renders something about a session (its tabs, its details), if it can be had
+pub type Previewer<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+// What the key that accepted the line asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyAction {
+    Attach,
+    ForceAttach,
+    Create,
+    Kill,
+    Rename,
+    Clone,
+    Adopt,
+    Details,
+    Preview,
+    Sort,
+    NextPage,
+    PrevPage,
+}
+
+// Accepts the line like Enter does, but remembers which action it stands for.
+// Keys that act on a session fall back to their usual readline meaning while
+// the line is still empty; plain characters only act on an empty line, and
+// are just typed otherwise.
+struct BoundKey {
+    action: KeyAction,
+    needs_input: bool,
+    needs_empty: bool,
+    pressed: Arc<Mutex<KeyAction>>,
+}
+
+impl ConditionalEventHandler for BoundKey {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        if self.needs_input && ctx.line().is_empty() {
+            return None;
+        }
+        if self.needs_empty && !ctx.line().is_empty() {
+            return None;
+        }
+        *self.pressed.lock().unwrap() = self.action;
+        Some(Cmd::AcceptLine)
+    }
+}
+
+fn bind(repl: &mut Editor<()>, key: Key, action: KeyAction, pressed: &Arc<Mutex<KeyAction>>) {
+    let event = match key {
+        Key::Ctrl(c) => KeyEvent::ctrl(c.to_ascii_uppercase()),
+        Key::Alt(c) => KeyEvent::alt(c),
+        Key::F(n) => KeyEvent(KeyCode::F(n), Modifiers::NONE),
+        Key::PageUp => KeyEvent(KeyCode::PageUp, Modifiers::NONE),
+        Key::PageDown => KeyEvent(KeyCode::PageDown, Modifiers::NONE),
+        Key::Char(c) => KeyEvent(KeyCode::Char(c), Modifiers::NONE),
+    };
+    // Paging would throw away what's typed
+    let needs_empty =
+        matches!(key, Key::Char(_)) || matches!(action, KeyAction::NextPage | KeyAction::PrevPage);
+    repl.bind_sequence(
+        event,
+        EventHandler::Conditional(Box::new(BoundKey {
+            action,
+            needs_input: !needs_empty
+                && !matches!(action, KeyAction::ForceAttach | KeyAction::Sort),
+            needs_empty,
+            pressed: Arc::clone(pressed),
+        })),
+    );
+}
+
+/// Running sessions first, then the resurrectable ones, each best first:
+/// the picker's list for `query`
+pub fn rank_all<'s>(
+    ranker: &Ranker,
+    query: &str,
+    sessions: &'s [String],
+    dead: &'s [String],
+) -> Vec<Ranked<'s>> {
+    let mut ranked = ranker.rank(query, sessions);
+    ranked.extend(ranker.rank(query, dead));
+    ranked
+}
+
+// Keeps each group together, in the order the groups first come up, and
+// the candidates within one in rank order
+fn group_candidates<'s>(
+    mut candidates: Vec<Ranked<'s>>,
+    labels: &HashMap<String, String>,
+) -> Vec<Ranked<'s>> {
+    if labels.is_empty() {
+        return candidates;
+    }
+    let mut order: Vec<Option<&String>> = Vec::new();
+    for candidate in &candidates {
+        let label = labels.get(candidate.name);
+        if !order.contains(&label) {
+            order.push(label);
+        }
+    }
+    candidates.sort_by_key(|candidate| {
+        let label = labels.get(candidate.name);
+        order.iter().position(|l| *l == label)
+    });
+    candidates
+}
+
+// `best` is what Enter would pick, when a query is in effect. What's
+// printed is noted on `screen`. Within `room` lines, if given, as many as
+// fit are printed from `start` on; where the next page would start comes
+// back.
+fn print_candidates(
+    candidates: &[Ranked],
+    dead: &[String],
+    best: Option<&str>,
+    options: &PickerOptions,
+    page: (usize, Option<usize>),
+    screen: &mut Screen,
+) -> usize {
+    let (start, room) = page;
+    let symbols = symbols::current();
+    let paint = |text: &str, color: fn(&Theme) -> Rgb| match &options.theme {
+        Some(theme) => theme::paint(text, color(theme)),
+        None => text.to_string(),
+    };
+    let is_dead = |name: &str| dead.iter().any(|d| d == name);
+    // Grouped lists have a heading per group instead
+    let first_dead = candidates
+        .iter()
+        .position(|c| is_dead(c.name))
+        .filter(|_| options.group_labels.is_empty());
+    // Ages line up in a column after the names, when there are any
+    let with_ages = candidates
+        .iter()
+        .any(|c| options.last_used.contains_key(c.name));
+    let label_width = format!("({})", candidates.len().saturating_sub(1)).len();
+    let name_width = candidates
+        .iter()
+        .map(|c| c.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let now = clock::now();
+    let print_note = |note: String, screen: &mut Screen| {
+        println!("{}", paint(&note, |t| t.accent));
+        screen.line(&note);
+    };
+    // What's left for candidates and their headings, with a line kept for
+    // saying there are more
+    let room = room.map(|room| room.saturating_sub(1 + usize::from(start > 0)));
+    if start > 0 {
+        print_note(
+            format!("({} above; {} shows them)", start, options.keys.prev_page),
+            screen,
+        );
+    }
+    let mut group = None;
+    let mut used = 0;
+    let mut end = start;
+    for (id, candidate) in candidates.iter().enumerate().skip(start) {
+        // A page starting among the exited sessions or partway into a group
+        // gets its heading again
+        let dead_heading =
+            first_dead.is_some_and(|first| id == first || (id == start && first < start));
+        let label = options
+            .group_labels
+            .get(candidate.name)
+            .filter(|label| group != Some(*label));
+        let height = 1 + usize::from(dead_heading) + usize::from(label.is_some());
+        if id > start && room.is_some_and(|room| used + height > room) {
+            break;
+        }
+        used += height;
+        end = id + 1;
+        if dead_heading {
+            let heading = "Resurrectable (exited, attach to bring back):";
+            println!("{}", heading);
+            screen.line(heading);
+        }
+        if let Some(label) = label {
+            let heading = format!("{}:", label);
+            println!("{}", paint(&heading, |t| t.accent));
+            screen.line(&heading);
+            group = Some(label);
+        }
+        let marker = if best == Some(candidate.name) {
+            paint(symbols.selected, |t| t.accent)
+        } else {
+            " ".repeat(symbols.selected.chars().count())
+        };
+        let (status, color): (_, fn(&Theme) -> Rgb) = if is_dead(candidate.name) {
+            (symbols.dead, |t| t.dead)
+        } else if options.attached.iter().any(|a| a == candidate.name) {
+            (symbols.attached, |t| t.attached)
+        } else {
+            (symbols.detached, |t| t.detached)
+        };
+        let mut label = format!("({})", id);
+        let mut name = format!("{} {}", status, candidate.name);
+        if with_ages {
+            label = format!("{:<width$}", label, width = label_width);
+            name = format!(
+                "{:<width$}",
+                name,
+                width = name_width + status.chars().count() + 1
+            );
+        }
+        let mut plain = format!("{} :: {}", label, name);
+        let mut line = format!(
+            "{}{} :: {}",
+            marker,
+            paint(&label, |t| t.accent),
+            paint(&name, color)
+        );
+        if let Some(at) = options.last_used.get(candidate.name) {
+            let age = details::ago(*at, now);
+            let column = symbols.selected.chars().count() + plain.chars().count() + 2;
+            screen.age(column, *at, age.clone());
+            let _ = write!(plain, "  {}", age);
+            let _ = write!(line, "  {}", age);
+        }
+        let groups: Vec<&str> = options
+            .groups
+            .iter()
+            .filter(|(_, members)| members.iter().any(|m| m == candidate.name))
+            .map(|(group, _)| group.as_str())
+            .collect();
+        if !groups.is_empty() {
+            let _ = write!(plain, "  [{}]", groups.join(", "));
+            let _ = write!(
+                line,
+                "  {}",
+                paint(&format!("[{}]", groups.join(", ")), |t| t.accent)
+            );
+        }
+        let aliases: Vec<&str> = options
+            .aliases
+            .iter()
+            .filter(|(_, session)| *session == candidate.name)
+            .map(|(alias, _)| alias.as_str())
+            .collect();
+        if !aliases.is_empty() {
+            let _ = write!(plain, "  aka {}", aliases.join(", "));
+            let _ = write!(line, "  aka {}", aliases.join(", "));
+        }
+        if options.explain_ranking {
+            let _ = write!(plain, "    [{}]", candidate.breakdown);
+            let _ = write!(line, "    [{}]", candidate.breakdown);
+        }
+        println!("{}", line);
+        screen.line(&format!(
+            "{}{}",
+            " ".repeat(symbols.selected.chars().count()),
+            plain
+        ));
+    }
+    if end < candidates.len() {
+        print_note(
+            format!(
+                "({} more; {} shows them)",
+                candidates.len() - end,
+                options.keys.next_page
+            ),
+            screen,
+        );
+    }
+    end
+}
+
+fn print_banner(summary: Option<&str>, keys: &KeysConfig, hide_help: bool) {
+    if let Some(summary) = summary {
+        println!("{}", summary);
+    }
+    if hide_help {
+        return;
+    }
+    println!(
+        "(Enter attaches, or creates a session named whatever's typed, or offers \
+         a made-up name if nothing is; instead of Enter: {} detaches any other \
+         clients first, {} creates exactly what's typed, {} kills (or purges an \
+         exited session), {} renames, {} clones, {} shows details, {} previews)",
+        keys.force_attach,
+        keys.create,
+        keys.kill,
+        keys.rename,
+        keys.clone,
+        keys.details,
+        keys.preview
+    );
+    println!(
+        "({} changes how the list is sorted and grouped, {} and {} page through a long one)",
+        keys.sort, keys.next_page, keys.prev_page
+    );
+}
+
+// Asks for one of `choices` by number, Enter keeping `current`; None if
+// the menu was backed out of
+fn choose_from(
+    repl: &mut Editor<()>,
+    what: &str,
+    choices: &[&str],
+    current: usize,
+    ctrl_c: CtrlC,
+) -> Result<Option<usize>, ReadlineError> {
+    let menu: Vec<String> = choices
+        .iter()
+        .enumerate()
+        .map(|(idx, choice)| format!("{}) {}", idx + 1, choice))
+        .collect();
+    println!("{}: {}", what, menu.join("  "));
+    loop {
+        let prompt = format!("{} (Enter keeps {}): ", what, choices[current]);
+        let Some(answer) = read(repl, &prompt, ctrl_c)? else {
+            return Ok(None);
+        };
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(Some(current));
+        }
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=choices.len()).contains(&n) => return Ok(Some(n - 1)),
+            _ => match choices.iter().position(|choice| *choice == answer) {
+                Some(idx) => return Ok(Some(idx)),
+                None => println!("Pick 1 to {}", choices.len()),
+            },
+        }
+    }
+}
+
+// The sort key and group-by menu
+fn arrange_menu(
+    repl: &mut Editor<()>,
+    options: &PickerOptions,
+) -> Result<Option<Outcome>, ReadlineError> {
+    let sort_keys: Vec<Option<SortKey>> = std::iter::once(None)
+        .chain(SortKey::value_variants().iter().copied().map(Some))
+        .collect();
+    let name = |value: Option<clap::PossibleValue>| {
+        value.map_or("?".to_string(), |v| v.get_name().to_string())
+    };
+    let sort_names: Vec<String> = sort_keys
+        .iter()
+        .map(|key| key.map_or("rank".to_string(), |key| name(key.to_possible_value())))
+        .collect();
+    let sort_names: Vec<&str> = sort_names.iter().map(String::as_str).collect();
+    let current = sort_keys
+        .iter()
+        .position(|k| *k == options.sort)
+        .unwrap_or(0);
+    let Some(sort) = choose_from(repl, "Sort by", &sort_names, current, options.ctrl_c)? else {
+        return Ok(None);
+    };
+    let groupings = GroupBy::value_variants();
+    let group_names: Vec<String> = groupings
+        .iter()
+        .map(|group_by| name(group_by.to_possible_value()))
+        .collect();
+    let group_names: Vec<&str> = group_names.iter().map(String::as_str).collect();
+    let current = groupings
+        .iter()
+        .position(|g| *g == options.group_by)
+        .unwrap_or(0);
+    let Some(group_by) = choose_from(repl, "Group by", &group_names, current, options.ctrl_c)?
+    else {
+        return Ok(None);
+    };
+    let answer = read(repl, "Make this the default? [y/N] ", options.ctrl_c)?.unwrap_or_default();
+    Ok(Some(Outcome::Arrange {
+        sort: sort_keys[sort],
+        group_by: groupings[group_by],
+        remember: answer.trim().eq_ignore_ascii_case("y"),
+    }))
+}
+
+fn print_preview(session: &str, preview: Previewer) -> String {
+    let panel = preview(session).unwrap_or_else(|| format!("(no preview for {})", session));
+    println!("{}", panel);
+    panel
+}
+
+// Adds the line to the history and the history file straight away, so it
+// survives however the picker ends
+fn remember(repl: &mut Editor<()>, line: &str, file: Option<&Path>) {
+    repl.add_history_entry(line);
+    if let Some(file) = file {
+        if let Some(dir) = file.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = repl.append_history(file);
+    }
+}
+
+// Reads a line, or None if Ctrl-C should just throw it away; otherwise
+// Ctrl-C comes back as ReadlineError::Interrupted, for leaving
+fn read(
+    repl: &mut Editor<()>,
+    prompt: &str,
+    ctrl_c: CtrlC,
+) -> Result<Option<String>, ReadlineError> {
+    match repl.readline(prompt) {
+        Err(ReadlineError::Interrupted) if ctrl_c == CtrlC::Clear => Ok(None),
+        line => line.map(Some),
+    }
+}
+
+// Resolves what was typed to a session: an exact name, an index
+// into the list on screen, or else the best match
+fn resolve<'s>(
+    feed: &str,
+    candidates: &[Ranked<'s>],
+    ranker: &Ranker,
+    sessions: &'s [String],
+    dead: &'s [String],
+) -> Option<&'s str> {
+    if let Some(session) = sessions.iter().chain(dead).find(|s| *s == feed) {
+        return Some(session);
+    }
+    if let Some(session) = feed.parse::<usize>().ok().and_then(|id| candidates.get(id)) {
+        return Some(session.name);
+    }
+    rank_all(ranker, feed, sessions, dead)
+        .first()
+        .map(|best| best.name)
+}
+
+pub fn interactive_select(
+    sessions: &[String],
+    dead: &[String],
+    ranker: &Ranker,
+    options: PickerOptions,
+    preview: Previewer,
+    details: Previewer,
+) -> Result<Outcome, ReadlineError> {
+    let mut query = options.initial_query.clone().unwrap_or_default();
+    let mut queries = Vec::new();
+    if !query.is_empty() {
+        queries.push(query.clone());
+        if options.accept_single_match {
+            if let [only] = rank_all(ranker, &query, sessions, dead).as_slice() {
+                return Ok(Outcome::Attach(Selection {
+                    name: only.name.to_string(),
+                    detach_others: false,
+                    queries,
+                    from_blueprint: false,
+                }));
+            }
+        }
+    }
+
+    if options.show_banner {
+        print_banner(options.summary.as_deref(), &options.keys, options.hide_help);
+    }
+    let mut repl = Editor::<()>::new()?;
+    let history_file = options.history_file.as_deref();
+    if history_file.is_none_or(|file| repl.load_history(file).is_err()) {
+        for query in &options.history {
+            repl.add_history_entry(query.as_str());
+        }
+    }
+    let pressed = Arc::new(Mutex::new(KeyAction::Attach));
+    let keys = &options.keys;
+    bind(
+        &mut repl,
+        keys.force_attach,
+        KeyAction::ForceAttach,
+        &pressed,
+    );
+    bind(&mut repl, keys.create, KeyAction::Create, &pressed);
+    bind(&mut repl, keys.kill, KeyAction::Kill, &pressed);
+    bind(&mut repl, keys.rename, KeyAction::Rename, &pressed);
+    bind(&mut repl, keys.clone, KeyAction::Clone, &pressed);
+    bind(&mut repl, keys.adopt, KeyAction::Adopt, &pressed);
+    bind(&mut repl, keys.details, KeyAction::Details, &pressed);
+    bind(&mut repl, keys.preview, KeyAction::Preview, &pressed);
+    bind(&mut repl, keys.sort, KeyAction::Sort, &pressed);
+    bind(&mut repl, keys.next_page, KeyAction::NextPage, &pressed);
+    bind(&mut repl, keys.prev_page, KeyAction::PrevPage, &pressed);
+    let mut watcher = options
+        .watch
+        .as_deref()
+        .map(|dir| Watcher::new(dir, PROBE_EVERY));
+    let mut printer = repl.create_external_printer().ok();
+    let mut announce = |notice: String| {
+        if let Some(printer) = &mut printer {
+            let _ = printer.print(notice);
+        }
+    };
+    // Whether the list on screen is out of date
+    let mut stale = false;
+    // Where the page on screen starts, and the ones before it did
+    let mut page = 0;
+    let mut earlier_pages = Vec::new();
+
+    // `query` is whatever the user typed last that matched more than one
+    // session; the list stays narrowed down to those until they type
+    // something else
+    let name: String = loop {
+        *pressed.lock().unwrap() = KeyAction::Attach;
+        let ranked = rank_all(ranker, &query, sessions, dead);
+        let best = ranked
+            .first()
+            .map(|best| best.name)
+            .filter(|_| !query.is_empty());
+        let candidates = group_candidates(ranked, &options.group_labels);
+        let mut screen = Screen::default();
+        // The prompt, and whatever was printed just before it, stay in view
+        let room = refresh::terminal_size().map(|(rows, _)| rows.saturating_sub(2));
+        let next_page =
+            print_candidates(&candidates, dead, best, &options, (page, room), &mut screen);
+        if options.auto_preview {
+            if let Some(best) = best {
+                screen.lines(&print_preview(best, preview));
+            }
+        }
+        let watch = watcher.as_mut().map(|watcher| Watch {
+            watcher,
+            announce: &mut announce,
+        });
+        let prompt = options.prompt.as_deref().map_or_else(
+            || ">>> ".to_string(),
+            |format| {
+                format
+                    .replace("{count}", &candidates.len().to_string())
+                    .replace("{running}", &sessions.len().to_string())
+                    .replace("{dead}", &dead.len().to_string())
+                    .replace("{query}", &query)
+            },
+        );
+        let (line, changed) =
+            refresh::while_reading(screen, watch, || read(&mut repl, &prompt, options.ctrl_c));
+        stale |= changed;
+        let Some(feed) = line? else {
+            query.clear();
+            page = 0;
+            earlier_pages.clear();
+            continue;
+        };
+        match *pressed.lock().unwrap() {
+            KeyAction::NextPage if next_page < candidates.len() => {
+                earlier_pages.push(page);
+                page = next_page;
+                continue;
+            }
+            KeyAction::PrevPage => {
+                page = earlier_pages.pop().unwrap_or(0);
+                continue;
+            }
+            KeyAction::NextPage => continue,
+            _ => (),
+        }
+        // A list narrowed down or widened out again starts over at the top
+        page = 0;
+        earlier_pages.clear();
+        if *pressed.lock().unwrap() == KeyAction::Sort {
+            match arrange_menu(&mut repl, &options)? {
+                Some(outcome) => return Ok(outcome),
+                None => continue,
+            }
+        }
+        if feed.is_empty() && stale {
+            return Ok(Outcome::Refresh);
+        }
+        // Enter on an empty line offers a made-up name for a new session,
+        // unless it's to widen a narrowed down list out again. It's asked
+        // about first, an Enter too many being easy to press.
+        if feed.is_empty() && !query.is_empty() {
+            query.clear();
+            continue;
+        }
+        if feed.is_empty() {
+            let name = names::memorable(sessions.iter().chain(dead));
+            let prompt = format!("Create a new session, {}? [y/N] ", name);
+            let answer = read(&mut repl, &prompt, options.ctrl_c)?.unwrap_or_default();
+            if answer.trim().eq_ignore_ascii_case("y") {
+                break name;
+            }
+            continue;
+        }
+        if feed.find(char::is_whitespace).is_some() {
+            continue;
+        }
+        remember(&mut repl, &feed, history_file);
+        let feed = options.aliases.get(&feed).cloned().unwrap_or(feed);
+
+        let action = *pressed.lock().unwrap();
+        let resolved = resolve(&feed, &candidates, ranker, sessions, dead);
+        match (action, resolved) {
+            (
+                KeyAction::Attach
+                | KeyAction::ForceAttach
+                | KeyAction::Create
+                | KeyAction::Sort
+                | KeyAction::NextPage
+                | KeyAction::PrevPage,
+                _,
+            ) => (),
+            (_, None) => {
+                println!("No session matches \"{}\"", feed);
+                continue;
+            }
+            (KeyAction::Preview, Some(session)) => {
+                print_preview(session, preview);
+                continue;
+            }
+            (KeyAction::Details, Some(session)) => {
+                print_preview(session, details);
+                continue;
+            }
+            (KeyAction::Kill, Some(session)) if dead.iter().any(|d| d == session) => {
+                let prompt = format!("Purge {} for good? [y/N] ", session);
+                let answer = read(&mut repl, &prompt, options.ctrl_c)?.unwrap_or_default();
+                if answer.trim().eq_ignore_ascii_case("y") {
+                    return Ok(Outcome::Purge(session.to_string()));
+                }
+                continue;
+            }
+            (KeyAction::Kill, Some(session)) => {
+                let prompt = format!("Kill {}? [y/N] ", session);
+                let answer = read(&mut repl, &prompt, options.ctrl_c)?.unwrap_or_default();
+                if answer.trim().eq_ignore_ascii_case("y") {
+                    return Ok(Outcome::Kill(session.to_string()));
+                }
+                continue;
+            }
+            (KeyAction::Rename, Some(from)) => {
+                if dead.iter().any(|d| d == from) {
+                    println!("{} has exited, attach to it to bring it back first", from);
+                    continue;
+                }
+                let prompt = format!("Rename {} to: ", from);
+                let to = read(&mut repl, &prompt, options.ctrl_c)?.unwrap_or_default();
+                if to.trim().is_empty() {
+                    continue;
+                }
+                return Ok(Outcome::Rename {
+                    from: from.to_string(),
+                    to: to.trim().to_string(),
+                });
+            }
+            (KeyAction::Adopt, Some(session)) => {
+                if dead.iter().any(|d| d == session) {
+                    println!(
+                        "{} has exited, attach to it to bring it back first",
+                        session
+                    );
+                    continue;
+                }
+                let prompt = format!("Tags for {} (and @set to add it to one): ", session);
+                let Some(words) = read(&mut repl, &prompt, options.ctrl_c)? else {
+                    continue;
+                };
+                let (sets, tags): (Vec<&str>, Vec<&str>) = words
+                    .split_whitespace()
+                    .partition(|word| word.starts_with('@'));
+                return Ok(Outcome::Adopt {
+                    session: session.to_string(),
+                    tags: tags.into_iter().map(str::to_string).collect(),
+                    sets: sets.iter().map(|set| set[1..].to_string()).collect(),
+                });
+            }
+            (KeyAction::Clone, Some(from)) => {
+                let prompt = format!("Clone {} as (Enter for {}-2 or so): ", from, from);
+                let Some(to) = read(&mut repl, &prompt, options.ctrl_c)? else {
+                    continue;
+                };
+                return Ok(Outcome::Clone {
+                    from: from.to_string(),
+                    to: Some(to.trim().to_string()).filter(|to| !to.is_empty()),
+                });
+            }
+        }
+
+        if action == KeyAction::Create {
+            if sessions.contains(&feed) {
+                println!("{} is already running", feed);
+                continue;
+            }
+            break feed;
+        }
+        if sessions.contains(&feed) || dead.contains(&feed) {
+            break feed;
+        }
+        if let Some(session) = feed.parse::<usize>().ok().and_then(|id| candidates.get(id)) {
+            break session.name.to_string();
+        }
+        queries.push(feed.clone());
+        match rank_all(ranker, &feed, sessions, dead).as_slice() {
+            [] => break feed,
+            [session] => break session.name.to_string(),
+            matches => {
+                println!(
+                    "{} sessions match \"{}\", pick one or keep typing to narrow it down:",
+                    matches.len(),
+                    feed
+                );
+                query = feed;
+            }
+        }
+    };
+
+    let detach_others = *pressed.lock().unwrap() == KeyAction::ForceAttach;
+    let mut from_blueprint = false;
+    if dead.contains(&name) {
+        print_preview(&name, preview);
+        let answer = repl.readline(
+            "Resurrect it as it was, or start it fresh from this layout \
+             without rerunning its commands? [R/f] ",
+        )?;
+        from_blueprint = answer.trim().eq_ignore_ascii_case("f");
+    }
+    Ok(Outcome::Attach(Selection {
+        name,
+        detach_others,
+        queries,
+        from_blueprint,
+    }))
+}
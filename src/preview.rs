@@ -0,0 +1,353 @@
+use crate::symbols;
+use std::fmt::Write;
+
+// One KDL node from `zellij action dump-layout`, just enough of it to tell
+// tabs, panes and what they run apart
+#[derive(Debug, Default)]
+struct Node {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn is_plugin(&self) -> bool {
+        self.children.iter().any(|c| c.name == "plugin")
+    }
+
+    fn leaf_panes<'a>(&'a self, into: &mut Vec<&'a Node>) {
+        for child in self.children.iter().filter(|c| c.name == "pane") {
+            if child.children.iter().any(|c| c.name == "pane") {
+                child.leaf_panes(into);
+            } else if !child.is_plugin() {
+                into.push(child);
+            }
+        }
+    }
+}
+
+// Splits a KDL line into its words, keeping quoted strings together
+fn words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn parse(layout: &str) -> Node {
+    let mut stack = vec![Node::default()];
+    for line in layout.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        if line == "}" {
+            if stack.len() > 1 {
+                let done = stack.pop().unwrap_or_default();
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(done);
+                }
+            }
+            continue;
+        }
+        let opens = line.ends_with('{');
+        let mut words = words(line.trim_end_matches('{')).into_iter();
+        let mut node = Node {
+            name: words.next().unwrap_or_default(),
+            ..Node::default()
+        };
+        for word in words {
+            match word.split_once('=') {
+                Some((k, v)) => node.attrs.push((k.to_string(), v.to_string())),
+                None => node.attrs.push((String::new(), word)),
+            }
+        }
+        // `pane { }` on a single line
+        let closes = line.ends_with("{ }") || line.ends_with("{}");
+        if opens && !closes {
+            stack.push(node);
+        } else if let Some(parent) = stack.last_mut() {
+            parent.children.push(node);
+        }
+    }
+    while stack.len() > 1 {
+        let done = stack.pop().unwrap_or_default();
+        if let Some(parent) = stack.last_mut() {
+            parent.children.push(done);
+        }
+    }
+    stack.pop().unwrap_or_default()
+}
+
+/// One tab of a running session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabSummary {
+    pub name: String,
+    pub focused: bool,
+    pub panes: usize,
+    /// What the tab's panes run, besides plain shells
+    pub commands: Vec<String>,
+}
+
+/// Summarizes the tabs in a dumped layout
+pub fn summarize(layout: &str) -> Vec<TabSummary> {
+    let root = parse(layout);
+    let layout = root
+        .children
+        .iter()
+        .find(|n| n.name == "layout")
+        .unwrap_or(&root);
+    layout
+        .children
+        .iter()
+        .filter(|n| n.name == "tab")
+        .enumerate()
+        .map(|(idx, tab)| {
+            let mut panes = Vec::new();
+            tab.leaf_panes(&mut panes);
+            let commands = panes
+                .iter()
+                .filter_map(|pane| {
+                    let command = pane.attr("command")?;
+                    let args = pane.children.iter().filter(|c| c.name == "args");
+                    let args = args.flat_map(|a| a.attrs.iter().map(|(_, v)| v.as_str()));
+                    Some(
+                        std::iter::once(command)
+                            .chain(args)
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    )
+                })
+                .collect();
+            TabSummary {
+                name: tab
+                    .attr("name")
+                    .map_or_else(|| format!("Tab #{}", idx + 1), str::to_string),
+                focused: tab.attr("focus") == Some("true"),
+                panes: panes.len(),
+                commands,
+            }
+        })
+        .collect()
+}
+
+/// The little box shown under the picker's list
+pub fn render(session: &str, tabs: &[TabSummary]) -> String {
+    let symbols = symbols::current();
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{} {} {} {} tab(s)",
+        symbols.box_top,
+        session,
+        symbols.box_rule,
+        tabs.len()
+    );
+    for (idx, tab) in tabs.iter().enumerate() {
+        let focus = if tab.focused { symbols.focus } else { "" };
+        let _ = write!(
+            out,
+            "{} {}. {}{}  {} pane(s)",
+            symbols.box_side,
+            idx + 1,
+            tab.name,
+            focus,
+            tab.panes
+        );
+        if !tab.commands.is_empty() {
+            let _ = write!(out, ": {}", tab.commands.join(", "));
+        }
+        out.push('\n');
+    }
+    out.push_str(symbols.box_bottom);
+    out
+}
+
+// What a pane runs, as opposed to how it's laid out
+const COMMAND_ATTRS: [&str; 2] = ["command", "edit"];
+const COMMAND_CHILDREN: [&str; 3] = ["args", "start_suspended", "close_on_exit"];
+
+fn without_attr(line: &str, key: &str) -> String {
+    let needle = format!(" {}=\"", key);
+    let Some(start) = line.find(&needle) else {
+        return line.to_string();
+    };
+    let value = start + needle.len();
+    match line[value..].find('"') {
+        Some(end) => format!("{}{}", &line[..start], &line[value + end + 1..]),
+        None => line.to_string(),
+    }
+}
+
+/// The same layout minus whatever its panes ran, so it comes back up with
+/// plain shells where the commands were
+pub fn strip_commands(layout: &str) -> String {
+    layout
+        .lines()
+        .filter(|line| {
+            let first = line.split_whitespace().next().unwrap_or_default();
+            !COMMAND_CHILDREN.contains(&first)
+        })
+        .map(|line| {
+            COMMAND_ATTRS
+                .iter()
+                .fold(line.to_string(), |line, key| without_attr(&line, key))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One of the layout's tabs (counting from 0) on its own, as a layout
+/// zellij can open
+pub fn extract_tab(layout: &str, index: usize) -> Option<String> {
+    let mut depth = 0;
+    let mut seen = 0;
+    let mut tab = Vec::new();
+    for line in layout.lines() {
+        let trimmed = line.trim();
+        if depth == 1 && (trimmed == "tab" || trimmed.starts_with("tab ")) {
+            if seen == index {
+                tab.push(line);
+            }
+            seen += 1;
+        } else if !tab.is_empty() {
+            tab.push(line);
+        }
+        if trimmed.ends_with('{') && !trimmed.ends_with("{ }") {
+            depth += 1;
+        } else if trimmed == "}" {
+            depth -= 1;
+        }
+        if !tab.is_empty() && depth <= 1 {
+            break;
+        }
+    }
+    if tab.is_empty() {
+        return None;
+    }
+    Some(format!("layout {{\n{}\n}}\n", tab.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYOUT: &str = r#"layout {
+    cwd "/home/me"
+    tab name="editor" focus=true {
+        pane size=1 borderless=true {
+            plugin location="zellij:tab-bar"
+        }
+        pane split_direction="vertical" {
+            pane command="nvim" {
+                args "src/main.rs"
+            }
+            pane
+        }
+    }
+    tab {
+        pane command="cargo" cwd="/home/me/src" {
+            args "watch" "-x" "test"
+            start_suspended true
+        }
+    }
+}
+"#;
+
+    #[test]
+    fn parse_nests_blocks_and_reads_attributes() {
+        let root = parse(LAYOUT);
+        assert_eq!(root.children.len(), 1);
+        let layout = &root.children[0];
+        assert_eq!(layout.name, "layout");
+        let tabs: Vec<_> = layout.children.iter().filter(|n| n.name == "tab").collect();
+        assert_eq!(tabs.len(), 2);
+        assert_eq!(tabs[0].attr("name"), Some("editor"));
+        assert_eq!(tabs[0].attr("focus"), Some("true"));
+        assert!(tabs[0].children[0].is_plugin());
+        assert_eq!(tabs[1].attr("name"), None);
+        let cwd = &layout.children[0];
+        assert_eq!(cwd.attrs, [(String::new(), "/home/me".to_string())]);
+    }
+
+    #[test]
+    fn parse_keeps_quoted_spaces_and_survives_unclosed_blocks() {
+        let root = parse("tab name=\"two words\" {\n    pane {}\n");
+        assert_eq!(root.children.len(), 1);
+        let tab = &root.children[0];
+        assert_eq!(tab.attr("name"), Some("two words"));
+        assert_eq!(tab.children.len(), 1);
+        assert!(tab.children[0].children.is_empty());
+    }
+
+    #[test]
+    fn summarize_counts_leaf_panes_and_their_commands() {
+        let tabs = summarize(LAYOUT);
+        assert_eq!(
+            tabs,
+            [
+                TabSummary {
+                    name: "editor".to_string(),
+                    focused: true,
+                    panes: 2,
+                    commands: vec!["nvim src/main.rs".to_string()],
+                },
+                TabSummary {
+                    name: "Tab #2".to_string(),
+                    focused: false,
+                    panes: 1,
+                    commands: vec!["cargo watch -x test".to_string()],
+                },
+            ]
+        );
+        assert!(summarize("").is_empty());
+    }
+
+    #[test]
+    fn strip_commands_leaves_the_layout_alone() {
+        let stripped = strip_commands(LAYOUT);
+        assert!(!stripped.contains("nvim"));
+        assert!(!stripped.contains("args"));
+        assert!(!stripped.contains("start_suspended"));
+        assert!(stripped.contains("pane cwd=\"/home/me/src\" {"));
+        assert!(stripped.contains("tab name=\"editor\" focus=true {"));
+        let tabs = summarize(&stripped);
+        assert_eq!(tabs.len(), 2);
+        assert_eq!(tabs[0].panes, 2);
+        assert!(tabs.iter().all(|tab| tab.commands.is_empty()));
+    }
+
+    #[test]
+    fn extract_tab_takes_one_tab_by_index() {
+        let second = extract_tab(LAYOUT, 1).unwrap();
+        assert!(second.starts_with("layout {\n    tab {\n"));
+        assert!(second.ends_with("    }\n}\n"));
+        let tabs = summarize(&second);
+        assert_eq!(tabs.len(), 1);
+        assert_eq!(tabs[0].commands, ["cargo watch -x test"]);
+        let first = summarize(&extract_tab(LAYOUT, 0).unwrap());
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].name, "editor");
+        assert_eq!(extract_tab(LAYOUT, 2), None);
+    }
+}
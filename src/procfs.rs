@@ -0,0 +1,183 @@
+use crate::locale;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Clock ticks per second in /proc's CPU times; USER_HZ is 100 on every
+/// architecture Linux exposes it on
+pub const TICKS_PER_SEC: u64 = 100;
+
+struct Process {
+    ppid: u32,
+    cpu_ticks: u64,
+    // The socket, for a zellij server (`zellij --server <socket>`)
+    serves: Option<PathBuf>,
+}
+
+/// Every process at one point in time, enough to find a session's server
+/// and everything running under it
+pub struct Snapshot {
+    processes: HashMap<u32, Process>,
+}
+
+/// What a session's server and everything under it use
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    /// CPU time used so far, in `TICKS_PER_SEC`
+    pub cpu_ticks: u64,
+    pub rss_kib: u64,
+    pub processes: usize,
+}
+
+impl Snapshot {
+    pub fn take() -> Self {
+        let processes = fs::read_dir("/proc")
+            .map(|dir| {
+                dir.flatten()
+                    .filter_map(|entry| {
+                        let pid = entry.file_name().to_str()?.parse().ok()?;
+                        Some((pid, read_process(&entry.path())?))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Snapshot { processes }
+    }
+
+    /// The pid of the server listening on `socket`
+    pub fn server_pid(&self, socket: &Path) -> Option<u32> {
+        self.processes
+            .iter()
+            .find(|(_, process)| process.serves.as_deref() == Some(socket))
+            .map(|(&pid, _)| pid)
+    }
+
+    /// Totals for `pid` and all its descendants
+    pub fn usage(&self, pid: u32) -> Usage {
+        let mut usage = Usage::default();
+        let mut pending = vec![pid];
+        while let Some(pid) = pending.pop() {
+            let Some(process) = self.processes.get(&pid) else {
+                continue;
+            };
+            usage.cpu_ticks += process.cpu_ticks;
+            usage.rss_kib += rss_kib(pid).unwrap_or(0);
+            usage.processes += 1;
+            pending.extend(
+                self.processes
+                    .iter()
+                    .filter(|(_, child)| child.ppid == pid)
+                    .map(|(&child, _)| child),
+            );
+        }
+        usage
+    }
+
+    /// The usage of the session whose socket is `socket`, if its server is
+    /// one of ours
+    pub fn session_usage(&self, socket: &Path) -> Option<Usage> {
+        self.server_pid(socket).map(|pid| self.usage(pid))
+    }
+}
+
+// /proc/<pid>/stat's fields, counted from after the command name since that
+// can hold spaces: the state is field 3, ppid 4, utime 14 and stime 15
+fn stat_fields(stat: &str) -> Option<Vec<&str>> {
+    Some(stat.rsplit_once(')')?.1.split_whitespace().collect())
+}
+
+fn field<'a>(fields: &[&'a str], number: usize) -> Option<&'a str> {
+    fields.get(number - 3).copied()
+}
+
+fn read_process(dir: &Path) -> Option<Process> {
+    let stat = fs::read_to_string(dir.join("stat")).ok()?;
+    let fields = stat_fields(&stat)?;
+    let ticks = |number| field(&fields, number)?.parse::<u64>().ok();
+    let cmdline = fs::read(dir.join("cmdline")).unwrap_or_default();
+    let mut args = cmdline.split(|&b| b == 0);
+    let serves = args
+        .find(|&arg| arg == b"--server")
+        .and_then(|_| args.next())
+        .map(|socket| PathBuf::from(String::from_utf8_lossy(socket).into_owned()));
+    Some(Process {
+        ppid: field(&fields, 4)?.parse().ok()?,
+        cpu_ticks: ticks(14)? + ticks(15)?,
+        serves,
+    })
+}
+
+fn rss_kib(pid: u32) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// The directory a process is in
+pub fn cwd(pid: u32) -> Option<PathBuf> {
+    fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+/// A process's niceness, scheduling policy and I/O class, e.g. "nice 10,
+/// batch, io idle"
+pub fn describe(pid: u32) -> Option<String> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let fields = stat_fields(&stat)?;
+    let nice = field(&fields, 19)?;
+    let policy = match field(&fields, 41)? {
+        "0" => "normal",
+        "1" => "fifo",
+        "2" => "round-robin",
+        "3" => "batch",
+        "5" => "idle",
+        "6" => "deadline",
+        _ => "unknown",
+    };
+    let mut out = format!("nice {}, {}", nice, policy);
+    if let Some(io) = io_class(pid) {
+        out.push_str(", io ");
+        out.push_str(&io);
+    }
+    Some(out)
+}
+
+// ionice prints "idle" or "best-effort: prio 4"; there's no /proc file for it
+fn io_class(pid: u32) -> Option<String> {
+    let output = Command::new("ionice")
+        .arg("-p")
+        .arg(pid.to_string())
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let class = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!class.is_empty()).then_some(class)
+}
+
+/// "512M", "1.5G" and the like
+pub fn format_kib(kib: u64) -> String {
+    match kib {
+        kib if kib < 1024 => format!("{}K", kib),
+        kib if kib < 1024 * 1024 => format!("{}M", kib / 1024),
+        kib => format!(
+            "{}G",
+            locale::current().decimal(kib as f64 / (1024.0 * 1024.0), 1)
+        ),
+    }
+}
+
+/// "3m12s", "2h05m" and the like
+pub fn format_ticks(ticks: u64) -> String {
+    match ticks / TICKS_PER_SEC {
+        secs if secs < 60 * 60 => format!("{}m{:02}s", secs / 60, secs % 60),
+        secs => format!(
+            "{}h{:02}m",
+            locale::current().count(secs / (60 * 60)),
+            secs / 60 % 60
+        ),
+    }
+}
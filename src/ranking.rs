@@ -0,0 +1,142 @@
+use crate::config::RankingConfig;
+use crate::matcher::Matcher;
+use crate::state::State;
+use std::fmt;
+
+/// How much each field contributed to a candidate's score, weights applied
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Breakdown {
+    pub name: f64,
+    pub tags: f64,
+    pub notes: f64,
+    pub cwd: f64,
+    pub frecency: f64,
+}
+
+impl Breakdown {
+    pub fn total(&self) -> f64 {
+        self.name + self.tags + self.notes + self.cwd + self.frecency
+    }
+}
+
+impl fmt::Display for Breakdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "name {:.1} + tags {:.1} + notes {:.1} + cwd {:.1} + frecency {:.1} = {:.1}",
+            self.name,
+            self.tags,
+            self.notes,
+            self.cwd,
+            self.frecency,
+            self.total()
+        )
+    }
+}
+
+pub struct Ranked<'s> {
+    pub name: &'s str,
+    pub breakdown: Breakdown,
+}
+
+/// Scores sessions on every field the chooser knows about, so a query can
+/// hit a tag or a note just as well as the name
+pub struct Ranker<'a> {
+    matcher: &'a Matcher,
+    weights: &'a RankingConfig,
+    state: &'a State,
+    now: u64,
+    /// Sessions that always come first, in exactly this order
+    pinned: Vec<String>,
+    /// The sessions come sorted already, so keep that order until there's
+    /// a query to rank them by
+    presorted: bool,
+}
+
+impl<'a> Ranker<'a> {
+    pub fn new(
+        matcher: &'a Matcher,
+        weights: &'a RankingConfig,
+        state: &'a State,
+        now: u64,
+    ) -> Self {
+        Self {
+            matcher,
+            weights,
+            state,
+            now,
+            pinned: Vec::new(),
+            presorted: false,
+        }
+    }
+
+    /// Lists these sessions first and in this order, whatever their score,
+    /// so a manifest's "second item" stays second
+    pub fn pin_order(mut self, pinned: Vec<String>) -> Self {
+        self.pinned = pinned;
+        self
+    }
+
+    pub fn presorted(mut self, presorted: bool) -> Self {
+        self.presorted = presorted;
+        self
+    }
+
+    fn breakdown(&self, query: &str, session: &str) -> Option<Breakdown> {
+        let record = self.state.sessions.get(session);
+        let score = |text: &str| self.matcher.score(query, text);
+
+        let name = score(session);
+        let tags = record.and_then(|r| r.tags.iter().filter_map(|tag| score(tag)).max());
+        let notes = record
+            .filter(|r| !r.notes.is_empty())
+            .and_then(|r| score(&r.notes));
+        let cwd = record
+            .and_then(|r| r.cwd.as_ref())
+            .and_then(|cwd| score(&cwd.to_string_lossy()));
+        if name.is_none() && tags.is_none() && notes.is_none() && cwd.is_none() {
+            return None;
+        }
+
+        // Raw frecency grows with every attach, the log keeps a handful of
+        // attaches from drowning out an actual match on the query
+        let frecency = record.map_or(0.0, |r| r.frecency(self.now).ln_1p() * 5.0);
+        let weigh = |s: Option<i64>, weight: f64| s.unwrap_or(0) as f64 * weight;
+        Some(Breakdown {
+            name: weigh(name, self.weights.name),
+            tags: weigh(tags, self.weights.tags),
+            notes: weigh(notes, self.weights.notes),
+            cwd: weigh(cwd, self.weights.cwd),
+            frecency: frecency * self.weights.frecency,
+        })
+    }
+
+    /// Sessions matching `query` on any field, best first
+    pub fn rank<'s>(&self, query: &str, sessions: &'s [String]) -> Vec<Ranked<'s>> {
+        let mut ranked: Vec<Ranked> = sessions
+            .iter()
+            .filter_map(|name| {
+                self.breakdown(query, name).map(|breakdown| Ranked {
+                    name: name.as_str(),
+                    breakdown,
+                })
+            })
+            .collect();
+        let pin = |name: &str| {
+            self.pinned
+                .iter()
+                .position(|p| p == name)
+                .unwrap_or(usize::MAX)
+        };
+        if self.presorted && query.is_empty() {
+            ranked.sort_by_key(|r| pin(r.name));
+        } else {
+            ranked.sort_by(|a, b| {
+                pin(a.name)
+                    .cmp(&pin(b.name))
+                    .then(b.breakdown.total().total_cmp(&a.breakdown.total()))
+            });
+        }
+        ranked
+    }
+}
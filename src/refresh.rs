@@ -0,0 +1,179 @@
+use crate::clock;
+use crate::details;
+use crate::watch::Watcher;
+use nix::libc;
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often to look for ages that went stale, and to check for being done
+const TICK: Duration = Duration::from_millis(200);
+const EVERY: Duration = Duration::from_secs(1);
+
+/// A "3m ago" on screen, kept so it can be rewritten on its own
+#[derive(Debug, Clone)]
+pub struct AgeCell {
+    /// Which of the lines printed since the list started it's on
+    pub line: usize,
+    /// How far into that line it starts, in columns
+    pub column: usize,
+    pub at: u64,
+    pub shown: String,
+}
+
+/// What the picker printed above its prompt: how wide each line was, and
+/// where the ages are among them
+#[derive(Debug, Default)]
+pub struct Screen {
+    widths: Vec<usize>,
+    cells: Vec<AgeCell>,
+}
+
+impl Screen {
+    pub fn line(&mut self, text: &str) {
+        self.widths.push(text.chars().count());
+    }
+
+    /// Every line of `text`, as printed with println!
+    pub fn lines(&mut self, text: &str) {
+        for line in text.lines() {
+            self.line(line);
+        }
+    }
+
+    /// An age on the line about to be recorded with `line`
+    pub fn age(&mut self, column: usize, at: u64, shown: String) {
+        self.cells.push(AgeCell {
+            line: self.widths.len(),
+            column,
+            at,
+            shown,
+        });
+    }
+
+    // Where each cell is now, as rows up from the prompt and a column,
+    // given the terminal's size. Lines wider than the terminal wrapped;
+    // cells that scrolled out of sight are None.
+    fn positions(&self, rows: usize, columns: usize) -> Vec<Option<(usize, usize)>> {
+        let height = |width: usize| width.div_ceil(columns).max(1);
+        self.cells
+            .iter()
+            .map(|cell| {
+                let below: usize = self.widths[cell.line + 1..]
+                    .iter()
+                    .map(|w| height(*w))
+                    .sum();
+                let up = below + height(self.widths[cell.line]) - cell.column / columns;
+                (up < rows).then_some((up, cell.column % columns))
+            })
+            .collect()
+    }
+}
+
+nix::ioctl_read_bad!(window_size, libc::TIOCGWINSZ, libc::winsize);
+
+/// The terminal's rows and columns, if stdout is one
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    unsafe { window_size(io::stdout().as_raw_fd(), &mut size) }.ok()?;
+    (size.ws_row > 0 && size.ws_col > 0).then_some((size.ws_row.into(), size.ws_col.into()))
+}
+
+// Rewrites just the cells whose age reads differently by now, leaving the
+// cursor (and whatever's being typed) where it was
+fn redraw(screen: &mut Screen, now: u64) {
+    let Some((rows, columns)) = terminal_size() else {
+        return;
+    };
+    let positions = screen.positions(rows, columns);
+    let mut out = String::new();
+    for (cell, position) in screen.cells.iter_mut().zip(positions) {
+        let age = details::ago(cell.at, now);
+        let Some((up, column)) = position.filter(|_| age != cell.shown) else {
+            continue;
+        };
+        // Padded over whatever was longer before
+        let width = cell.shown.chars().count().max(age.chars().count());
+        out.push_str(&format!("\x1b7\x1b[{}A\r", up));
+        if column > 0 {
+            out.push_str(&format!("\x1b[{}C", column));
+        }
+        out.push_str(&format!("{:<width$}\x1b8", age, width = width));
+        cell.shown = age;
+    }
+    if !out.is_empty() {
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(out.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+/// Sessions to look out for while the prompt waits, and how to tell the
+/// user about them without disturbing what they're typing
+pub struct Watch<'a> {
+    pub watcher: &'a mut Watcher,
+    pub announce: &'a mut (dyn FnMut(String) + Send),
+}
+
+// A line for above the prompt about sessions that came and went
+fn notice(started: &[String], gone: &[String]) -> String {
+    let changes: Vec<String> = started
+        .iter()
+        .map(|s| format!("+ {}", s))
+        .chain(gone.iter().map(|s| format!("- {}", s)))
+        .collect();
+    format!(
+        "({} since the list was drawn; Enter on an empty line updates it)",
+        changes.join(", ")
+    )
+}
+
+/// Runs `read` (the prompt), keeping the ages in `screen` current in the
+/// meantime. Nothing is fetched again: the times are what they were when
+/// the list was drawn, only "now" moves on. With `watch`, sessions starting
+/// or going away are announced as they do; whether any did comes back
+/// along with what `read` returned.
+pub fn while_reading<T>(
+    mut screen: Screen,
+    mut watch: Option<Watch>,
+    read: impl FnOnce() -> T,
+) -> (T, bool) {
+    if (screen.cells.is_empty() && watch.is_none()) || !io::stdout().is_terminal() {
+        return (read(), false);
+    }
+    let done = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let changes = scope.spawn(|| {
+            let mut changed = false;
+            let mut last = Instant::now();
+            while !done.load(Ordering::Relaxed) {
+                match &mut watch {
+                    Some(watch) => {
+                        if let Some((started, gone)) = watch.watcher.wait(TICK) {
+                            let notice = notice(&started, &gone);
+                            screen.line(&notice);
+                            (watch.announce)(notice);
+                            changed = true;
+                        }
+                    }
+                    None => thread::sleep(TICK),
+                }
+                if last.elapsed() >= EVERY {
+                    redraw(&mut screen, clock::now());
+                    last = Instant::now();
+                }
+            }
+            changed
+        });
+        let result = read();
+        done.store(true, Ordering::Relaxed);
+        (result, changes.join().unwrap_or(false))
+    })
+}
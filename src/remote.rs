@@ -0,0 +1,314 @@
+use crate::cli::{CliArgs, Command as CliCommand};
+use crate::clock;
+use crate::config::Config;
+use crate::failure::Failure;
+use crate::matcher::Matcher;
+use crate::picker::{interactive_select, Outcome, PickerOptions};
+use crate::preview;
+use crate::ranking::Ranker;
+use crate::state::State;
+use crate::theme::Theme;
+use crate::zellij::{Feature, Version};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+
+// ssh's own exit status when it couldn't connect (or the like)
+const SSH_FAILED: i32 = 255;
+// What a shell exits with when the command isn't on its $PATH
+const NOT_FOUND: i32 = 127;
+
+/// Another machine's zellij, driven over SSH
+pub struct Remote {
+    pub host: String,
+    /// The zellij binary there, looked up on the remote `$PATH` unless it's
+    /// a path: `--zellij-bin`, or else `zellij`
+    pub bin: String,
+    /// Whatever came after `--` on our command line, passed on in front of
+    /// zellij's subcommand as it is locally
+    pub extra_args: Vec<String>,
+    // The remote zellij's version, once it's been asked for; None if it
+    // couldn't be made out
+    version: OnceLock<Option<Version>>,
+}
+
+// Quotes `word` for the remote shell, which ssh hands the command line to
+fn quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+// `list-sessions` colours the names and follows them with when they were
+// created and whether they've exited (on newer zellij), or with
+// "(current)" from inside a session; exited ones aren't running
+fn parse_sessions(stdout: &str) -> Vec<String> {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    let ansi = ANSI.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap());
+    stdout
+        .lines()
+        .map(|line| ansi.replace_all(line, ""))
+        .filter(|line| !line.contains("(EXITED"))
+        .filter_map(|line| {
+            let name = line.split(" [Created ").next()?;
+            let name = name.trim().trim_end_matches("(current)").trim();
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect()
+}
+
+impl Remote {
+    pub fn new(host: String, bin: String, extra_args: Vec<String>) -> Self {
+        Remote {
+            host,
+            bin,
+            extra_args,
+            version: OnceLock::new(),
+        }
+    }
+
+    // The remote command line for `zellij <args>`, quoted
+    fn command_line(&self, args: &[&str]) -> Vec<String> {
+        std::iter::once(self.bin.as_str())
+            .chain(self.extra_args.iter().map(String::as_str))
+            .chain(args.iter().copied())
+            .map(quote)
+            .collect()
+    }
+
+    // Runs `zellij <args>` on the host, without a terminal
+    fn zellij(&self, args: &[&str]) -> io::Result<Output> {
+        let remote = self.command_line(args);
+        log::debug!("Running {} on {}", remote.join(" "), self.host);
+        Command::new("ssh")
+            .arg(&self.host)
+            .args(&remote)
+            .stdin(Stdio::null())
+            .output()
+    }
+
+    fn version(&self) -> Option<Version> {
+        *self.version.get_or_init(|| {
+            let output = Command::new("ssh")
+                .arg(&self.host)
+                .arg(quote(&self.bin))
+                .arg("--version")
+                .stdin(Stdio::null())
+                .stderr(Stdio::null())
+                .output()
+                .ok()?;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            Version::parse(stdout.split_whitespace().last()?)
+        })
+    }
+
+    // Like `Zellij::require`, for the zellij over there
+    fn require(&self, feature: Feature) -> io::Result<()> {
+        match self.version() {
+            Some(version) if version >= feature.since() => Ok(()),
+            version => Err(feature.unsupported(version)),
+        }
+    }
+
+    fn failure(&self, output: &Output, doing: &str) -> Failure {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        match output.status.code() {
+            Some(SSH_FAILED) => Failure::Other(format!("Couldn't reach {}: {}", self.host, stderr)),
+            Some(NOT_FOUND) => Failure::Zellij(format!(
+                "There's no {} on {}'s $PATH (for non-interactive shells)",
+                self.bin, self.host
+            )),
+            _ => Failure::Ipc(format!("Couldn't {} on {}: {}", doing, self.host, stderr)),
+        }
+    }
+
+    /// The sessions running on the host, from `zellij list-sessions`
+    pub fn sessions(&self) -> Result<Vec<String>, Failure> {
+        let output = self.zellij(&["list-sessions"]).map_err(|err| {
+            Failure::Other(format!("Couldn't run ssh to reach {}: {}", self.host, err))
+        })?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // With nothing running, zellij says so on stderr and exits with 1
+        if output.status.code() == Some(1) && stdout.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        if !output.status.success() {
+            return Err(self.failure(&output, "list the sessions"));
+        }
+        Ok(parse_sessions(&stdout))
+    }
+
+    /// The session's current tabs and panes, as KDL
+    pub fn dump_layout(&self, session: &str) -> io::Result<String> {
+        self.require(Feature::DumpLayout)?;
+        let output = self.zellij(&["--session", session, "action", "dump-layout"])?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "`zellij action dump-layout` exited with {}",
+                output.status
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    pub fn kill_session(&self, session: &str) -> Result<(), Failure> {
+        log::info!("Killing {:?} on {}", session, self.host);
+        let output = self
+            .zellij(&["kill-session", session])
+            .map_err(|err| Failure::Other(format!("Couldn't run ssh: {}", err)))?;
+        if !output.status.success() {
+            return Err(self.failure(&output, &format!("kill \"{}\"", session)));
+        }
+        Ok(())
+    }
+
+    /// Replaces us with `ssh -t host zellij attach -c <session>`, only
+    /// returning if that couldn't be started
+    pub fn attach(&self, session: &str) -> io::Error {
+        log::info!("Attaching to {:?} on {}", session, self.host);
+        Command::new("ssh")
+            .arg("-t")
+            .arg(&self.host)
+            .args(self.command_line(&["attach", "-c", session]))
+            .exec()
+    }
+}
+
+/// `--remote`: picks one of the host's sessions (or lists them, with
+/// `list`) and attaches to it over SSH. Nothing about them is remembered
+/// locally, since names on different hosts would get mixed up.
+pub fn run(
+    remote: &Remote,
+    opts: &CliArgs,
+    config: &Config,
+    theme: Option<Theme>,
+) -> Result<(), Failure> {
+    let mut sessions = remote.sessions()?;
+    if let Some(filter) = &opts.filter {
+        sessions.retain(|session| filter.matches(session));
+    }
+    match &opts.command {
+        Some(CliCommand::List { json: true, .. }) => {
+            return Err(Failure::Other(
+                "`list --json` only works on this machine".to_string(),
+            ))
+        }
+        Some(CliCommand::List { .. }) => {
+            for session in &sessions {
+                println!("{}", session);
+            }
+            return Ok(());
+        }
+        Some(_) => {
+            return Err(Failure::Other(
+                "Only `list` works with --remote, besides picking a session".to_string(),
+            ))
+        }
+        None => (),
+    }
+
+    let name = match &opts.session {
+        // The config's aliases name sessions here, not over there
+        Some(name) => crate::expand_prefix(name, &sessions, opts.exact, &BTreeMap::new())?,
+        None => pick(remote, opts, config, theme, sessions)?,
+    };
+    if opts.print {
+        return writeln!(io::stdout(), "{}", name)
+            .map_err(|err| Failure::Other(format!("Couldn't print the selection: {}", err)));
+    }
+    let err = remote.attach(&name);
+    Err(Failure::Other(format!("Couldn't run ssh: {}", err)))
+}
+
+fn pick(
+    remote: &Remote,
+    opts: &CliArgs,
+    config: &Config,
+    theme: Option<Theme>,
+    mut sessions: Vec<String>,
+) -> Result<String, Failure> {
+    let matcher = Matcher::new(&config.matching);
+    let state = State::default();
+    let mut first_run = true;
+    loop {
+        let options = PickerOptions {
+            show_banner: first_run && !opts.quiet,
+            hide_help: config.prompt.hide_help,
+            prompt: config.prompt.format.clone(),
+            theme,
+            explain_ranking: opts.explain_ranking,
+            initial_query: opts.query.clone().filter(|_| first_run),
+            accept_single_match: first_run && opts.query.is_some(),
+            auto_preview: !opts.no_preview,
+            keys: config.keys.clone(),
+            ctrl_c: config.ctrl_c,
+            ..PickerOptions::default()
+        };
+        first_run = false;
+        let ranker = Ranker::new(&matcher, &config.ranking, &state, clock::now());
+        let preview = |session: &str| {
+            let layout = match remote.dump_layout(session) {
+                Ok(layout) => layout,
+                Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+                    return Some(format!("No preview: {}", err));
+                }
+                Err(_) => return None,
+            };
+            Some(preview::render(session, &preview::summarize(&layout)))
+        };
+        let details = |_: &str| -> Option<String> { None };
+        match interactive_select(&sessions, &[], &ranker, options, &preview, &details)? {
+            Outcome::Attach(selection) => return Ok(selection.name),
+            Outcome::Kill(session) => {
+                remote.kill_session(&session)?;
+                sessions.retain(|s| *s != session);
+            }
+            Outcome::Refresh => (),
+            Outcome::Rename { .. }
+            | Outcome::Clone { .. }
+            | Outcome::Adopt { .. }
+            | Outcome::Purge(_)
+            | Outcome::Arrange { .. } => {
+                println!("Only attaching and killing work with --remote");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_lists_of_old_and_new_zellij_come_down_to_names() {
+        assert_eq!(parse_sessions("work\nplay (current)\n\n"), ["work", "play"]);
+        let newer = "\x1b[32;1mwork\x1b[m [Created \x1b[35;1m2h 3m\x1b[m ago] \n\
+                     \x1b[32;1mplay\x1b[m [Created \x1b[35;1m10s\x1b[m ago] (\x1b[32;1mcurrent\x1b[m)\n\
+                     \x1b[32;1mold\x1b[m [Created \x1b[35;1m3days\x1b[m ago] (\x1b[31;1mEXITED\x1b[m - attach to resurrect)\n";
+        assert_eq!(parse_sessions(newer), ["work", "play"]);
+        let plain =
+            "work [Created 2h 3m ago] \nold [Created 3days ago] (EXITED - attach to resurrect)\n";
+        assert_eq!(parse_sessions(plain), ["work"]);
+    }
+
+    #[test]
+    fn the_remote_command_line_carries_the_binary_and_extra_args() {
+        let remote = Remote::new(
+            "me@devbox".to_string(),
+            "/opt/zellij/bin/zellij".to_string(),
+            vec!["--config".to_string(), "it's.kdl".to_string()],
+        );
+        assert_eq!(
+            remote.command_line(&["attach", "-c", "work"]),
+            [
+                "'/opt/zellij/bin/zellij'",
+                "'--config'",
+                r"'it'\''s.kdl'",
+                "'attach'",
+                "'-c'",
+                "'work'"
+            ]
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use crate::dryrun;
+use crate::zellij::{Feature, Zellij};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use std::{fs, io};
+use zellij_utils::{
+    consts::{VERSION, ZELLIJ_CACHE_DIR, ZELLIJ_SOCK_DIR},
+    data::ClientId,
+    interprocess::local_socket::LocalSocketStream,
+    ipc::{ClientToServerMsg, IpcReceiverWithContext, IpcSenderWithContext, ServerToClientMsg},
+};
+
+/// Where the servers we can talk to keep their sockets. Like zellij itself,
+/// a custom base directory gets a per-version subdirectory.
+pub fn socket_dir(base: Option<&Path>) -> PathBuf {
+    match base {
+        Some(base) => base.join(VERSION),
+        None => ZELLIJ_SOCK_DIR.clone(),
+    }
+}
+
+// What zellij serializes a session into so it can be resurrected
+const RESURRECTION_LAYOUT: &str = "session-layout.kdl";
+
+static RESURRECTION_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Works out where the zellij we run keeps the sessions it can resurrect:
+/// under its own version in the cache, not the one we were built against.
+/// A zellij too old to resurrect anything has no such place.
+pub fn init_resurrection(zellij: &Zellij) {
+    let dir = match zellij.version_number() {
+        Ok(version) if zellij.supports(Feature::Resurrection) => {
+            Some(ZELLIJ_CACHE_DIR.join(version).join("session_info"))
+        }
+        _ => None,
+    };
+    let _ = RESURRECTION_DIR.set(dir);
+}
+
+// Where zellij keeps the serialized sessions, one directory each
+fn resurrection_dir() -> io::Result<&'static Path> {
+    RESURRECTION_DIR
+        .get()
+        .and_then(Option::as_deref)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no exited sessions to be had"))
+}
+
+/// Sessions whose server is gone but that zellij can bring back from its
+/// cache, leaving out any that are in fact still running
+pub fn get_dead_sessions(running: &[String]) -> Vec<String> {
+    let Ok(entries) = resurrection_dir().and_then(fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut dead: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join(RESURRECTION_LAYOUT).is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !running.contains(name))
+        .collect();
+    dead.sort();
+    dead
+}
+
+/// Deletes what zellij saved to resurrect a session, for good
+pub fn purge_dead_session(name: &str) -> io::Result<()> {
+    if dryrun::pretend(format_args!("purge {}", name)) {
+        return Ok(());
+    }
+    log::info!("Purging {:?}", name);
+    fs::remove_dir_all(resurrection_dir()?.join(name))
+}
+
+/// Roughly when the session's server started (when it put its socket in
+/// place), or for an exited one, when zellij last saved it
+pub fn created_at(socket_dir: &Path, name: &str) -> Option<SystemTime> {
+    fs::metadata(socket_dir.join(name))
+        .or_else(|_| fs::metadata(resurrection_dir()?.join(name)))
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// The layout a dead session will be resurrected with
+pub fn dead_session_layout(name: &str) -> io::Result<String> {
+    fs::read_to_string(resurrection_dir()?.join(name).join(RESURRECTION_LAYOUT))
+}
+
+/// What probing a session's socket found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionStatus {
+    /// The server answered, and has this many clients attached
+    Alive { clients: usize },
+    /// Something took the connection but didn't answer in time: a hung
+    /// server, or a very busy one. Its socket is left alone.
+    Unresponsive,
+    /// The server answered with something our protocol doesn't expect
+    VersionMismatch,
+    /// Nothing was listening anymore, so the socket it left behind was
+    /// removed
+    DeadSocketRemoved,
+}
+
+impl SessionStatus {
+    pub fn is_alive(self) -> bool {
+        matches!(self, SessionStatus::Alive { .. })
+    }
+
+    /// How a session whose socket is there but that can't be used is
+    /// pointed out
+    pub fn trouble(self) -> Option<&'static str> {
+        match self {
+            SessionStatus::Unresponsive => Some("not answering"),
+            SessionStatus::VersionMismatch => Some("another zellij version"),
+            SessionStatus::Alive { .. } | SessionStatus::DeadSocketRemoved => None,
+        }
+    }
+}
+
+// How long a server gets to answer a probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Every session socket in the directory, by name
+fn socket_names(socket_dir: &Path) -> io::Result<Vec<String>> {
+    match fs::read_dir(socket_dir) {
+        Ok(files) => Ok(files
+            .flatten()
+            .filter(|file| file.file_type().is_ok_and(|kind| kind.is_socket()))
+            .filter_map(|file| file.file_name().into_string().ok())
+            .collect()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+// Retrieved from Zellij
+// https://github.com/zellij-org/zellij/blob/main/src/sessions.rs
+pub fn get_sessions(socket_dir: &Path) -> Result<Vec<String>, io::ErrorKind> {
+    let names = socket_names(socket_dir).map_err(|err| err.kind())?;
+    Ok(names
+        .into_iter()
+        .filter(|name| probe(socket_dir, name).is_some_and(SessionStatus::is_alive))
+        .collect())
+}
+
+/// Sessions with a socket that can't be used, leaving out the `running`
+/// ones already known to be fine
+pub fn troubled(socket_dir: &Path, running: &[String]) -> Vec<(String, SessionStatus)> {
+    socket_names(socket_dir)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|name| !running.contains(name))
+        .filter_map(|name| {
+            let status = probe(socket_dir, &name)?;
+            status.trouble().map(|_| (name, status))
+        })
+        .collect()
+}
+
+/// Asks the server behind the session's socket who's attached; None when
+/// there's no socket at all. Only a socket nothing listens on anymore is
+/// removed: one that's merely slow to answer may yet recover.
+pub fn probe(socket_dir: &Path, name: &str) -> Option<SessionStatus> {
+    let path = &*socket_dir.join(name);
+    log::trace!("Probing {}", path.display());
+    let stream = match UnixStream::connect(path) {
+        Ok(stream) => stream,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+            if dryrun::pretend(format_args!("remove the stale socket {}", path.display())) {
+                return Some(SessionStatus::DeadSocketRemoved);
+            }
+            log::info!("Removing the stale socket {}", path.display());
+            return match fs::remove_file(path) {
+                Ok(()) => Some(SessionStatus::DeadSocketRemoved),
+                Err(err) => {
+                    log::warn!("Couldn't remove {}: {}", path.display(), err);
+                    Some(SessionStatus::Unresponsive)
+                }
+            };
+        }
+        Err(err) => {
+            log::debug!("Couldn't connect to {}: {}", path.display(), err);
+            return Some(SessionStatus::Unresponsive);
+        }
+    };
+    if let Err(err) = stream
+        .set_read_timeout(Some(PROBE_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(PROBE_TIMEOUT)))
+    {
+        log::debug!("Couldn't set a timeout on {}: {}", path.display(), err);
+    }
+    // Safe: the descriptor is moved out of `stream`, so nothing else owns it
+    let stream = unsafe { LocalSocketStream::from_raw_fd(stream.into_raw_fd()) };
+    let mut sender = IpcSenderWithContext::new(stream);
+    let mut receiver: IpcReceiverWithContext<ServerToClientMsg> = sender.get_receiver();
+    if sender.send(ClientToServerMsg::ListClients).is_err() {
+        return Some(SessionStatus::Unresponsive);
+    }
+    match receiver.recv() {
+        Some((ServerToClientMsg::ActiveClients(clients), _)) => Some(SessionStatus::Alive {
+            clients: clients.len(),
+        }),
+        Some((reply, _)) => {
+            log::debug!("{:?} answered {:?} to ListClients", name, reply);
+            Some(SessionStatus::VersionMismatch)
+        }
+        None => {
+            log::debug!("{:?} hung up or timed out without answering", name);
+            Some(SessionStatus::Unresponsive)
+        }
+    }
+}
+
+fn ipc_error(msg: &str) -> io::Error {
+    log::debug!("IPC failed: {}", msg);
+    io::Error::new(io::ErrorKind::BrokenPipe, msg.to_string())
+}
+
+// Asks the server which clients are attached, leaving the connection open
+// for whatever the caller wants to tell it next
+fn list_clients(
+    socket_dir: &Path,
+    name: &str,
+) -> io::Result<(IpcSenderWithContext<ClientToServerMsg>, Vec<ClientId>)> {
+    let path = &*socket_dir.join(name);
+    let stream = LocalSocketStream::connect(path)?;
+    let mut sender = IpcSenderWithContext::new(stream);
+    let mut receiver: IpcReceiverWithContext<ServerToClientMsg> = sender.get_receiver();
+    sender
+        .send(ClientToServerMsg::ListClients)
+        .map_err(ipc_error)?;
+    match receiver.recv() {
+        Some((ServerToClientMsg::ActiveClients(clients), _)) => {
+            log::trace!("{:?} has clients {:?}", name, clients);
+            Ok((sender, clients))
+        }
+        None | Some((_, _)) => Err(ipc_error("Unexpected reply while listing clients")),
+    }
+}
+
+/// Who's attached to the session right now
+pub fn client_ids(socket_dir: &Path, name: &str) -> io::Result<Vec<ClientId>> {
+    list_clients(socket_dir, name).map(|(_, clients)| clients)
+}
+
+/// Whether anyone is attached to the session right now
+pub fn is_attached(socket_dir: &Path, name: &str) -> bool {
+    matches!(probe(socket_dir, name), Some(SessionStatus::Alive { clients }) if clients > 0)
+}
+
+// Asks the server for every client attached to the session and
+// kicks them all off, so we're the only one left once we attach
+pub fn detach_other_clients(socket_dir: &Path, name: &str) -> io::Result<()> {
+    let (mut sender, clients) = list_clients(socket_dir, name)?;
+    if clients.is_empty() {
+        return Ok(());
+    }
+    if dryrun::pretend(format_args!(
+        "detach {} client(s) from {}",
+        clients.len(),
+        name
+    )) {
+        return Ok(());
+    }
+    log::info!("Detaching {} client(s) from {:?}", clients.len(), name);
+    sender
+        .send(ClientToServerMsg::DetachSession(clients))
+        .map_err(ipc_error)
+}
+
+/// Refuses a name that can't be a file name by itself: zellij needs it to
+/// be one for the socket, and so does what's kept per session besides
+pub fn check_name(session: &str) -> io::Result<()> {
+    if matches!(session, "" | "." | "..") || session.contains(['/', '\0']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} isn't a valid session name", session),
+        ));
+    }
+    Ok(())
+}
+
+/// Held while a session is being created, so that two choosers creating
+/// the same name at once take turns; let go of when dropped
+pub struct CreationLock {
+    _file: fs::File,
+}
+
+/// Takes the lock on creating `session`, kept in `dir`, calling `waiting`
+/// first if someone else has it
+pub fn lock_creation(
+    dir: &Path,
+    session: &str,
+    waiting: impl FnOnce(),
+) -> io::Result<CreationLock> {
+    check_name(session)?;
+    fs::create_dir_all(dir)?;
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(format!("{}.lock", session)))?;
+    match file.try_lock() {
+        Ok(()) => (),
+        Err(fs::TryLockError::WouldBlock) => {
+            waiting();
+            file.lock()?;
+        }
+        Err(fs::TryLockError::Error(err)) => return Err(err),
+    }
+    Ok(CreationLock { _file: file })
+}
+
+// Looks for a new server's socket often at first, when it usually shows up,
+// and less and less often after that
+const FIRST_LOOK: Duration = Duration::from_millis(10);
+const MAX_BETWEEN_LOOKS: Duration = Duration::from_millis(500);
+
+/// Waits for `session`'s server to answer on its socket, giving up after
+/// `timeout`
+pub fn wait_for_socket(socket_dir: &Path, session: &str, timeout: Duration) -> io::Result<()> {
+    let path = socket_dir.join(session);
+    let started = Instant::now();
+    log::debug!("Waiting up to {:?} for {}", timeout, path.display());
+    let mut between = FIRST_LOOK;
+    loop {
+        if is_socket(&path) && probe(socket_dir, session).is_some_and(SessionStatus::is_alive) {
+            log::debug!("{:?} came up after {:?}", session, started.elapsed());
+            return Ok(());
+        }
+        let left = timeout.saturating_sub(started.elapsed());
+        if left.is_zero() {
+            break;
+        }
+        thread::sleep(between.min(left));
+        between = (between * 2).min(MAX_BETWEEN_LOOKS);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("no socket showed up at {}", path.display()),
+    ))
+}
+
+fn is_socket(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|meta| meta.file_type().is_socket())
+        .unwrap_or(false)
+}
@@ -0,0 +1,80 @@
+use nix::sys::signal::{self, Signal};
+use nix::sys::termios::{self, SetArg};
+use nix::unistd::Pid;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::io::{self, IsTerminal};
+use std::os::unix::io::AsRawFd;
+use std::process::{self, Child, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// Until we hand the terminal over to zellij, dying to SIGINT, SIGTERM or
+/// SIGHUP first puts the terminal back the way we found it (the prompt may
+/// have left it in raw mode), then exits the way the signal would have
+pub fn restore_terminal_on_exit() {
+    let stdin = io::stdin().as_raw_fd();
+    let saved = io::stdin()
+        .is_terminal()
+        .then(|| termios::tcgetattr(stdin).ok())
+        .flatten();
+    let mut signals = match Signals::new([SIGINT, SIGTERM, SIGHUP]) {
+        Ok(signals) => signals,
+        Err(err) => {
+            log::warn!("Couldn't set up signal handling: {}", err);
+            return;
+        }
+    };
+    thread::spawn(move || {
+        if let Some(caught) = signals.forever().next() {
+            log::info!("Caught signal {}, exiting", caught);
+            if let Some(saved) = &saved {
+                let _ = termios::tcsetattr(stdin, SetArg::TCSANOW, saved);
+            }
+            // Whatever was half drawn (a prompt, a spinner) gets its own line
+            eprintln!();
+            process::exit(128 + caught);
+        }
+    });
+}
+
+/// Notes the last SIGINT, SIGTERM or SIGHUP to arrive from now on (0 while
+/// none has). Unlike a thread waiting on them, this survives a fork, so the
+/// forked process still knows it was told to stop.
+pub fn pending() -> io::Result<Arc<AtomicUsize>> {
+    let pending = Arc::new(AtomicUsize::new(0));
+    for caught in [SIGINT, SIGTERM, SIGHUP] {
+        signal_hook::flag::register_usize(caught, Arc::clone(&pending), caught as usize)?;
+    }
+    Ok(pending)
+}
+
+/// Waits for `child`, passing on any SIGINT, SIGTERM, SIGHUP or SIGQUIT we
+/// get meanwhile, so stopping us stops it too rather than orphaning it. One
+/// that was `pending` while the child started is passed on first.
+pub fn wait_forwarding(child: &mut Child, pending: &AtomicUsize) -> io::Result<ExitStatus> {
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP, SIGQUIT])?;
+    let handle = signals.handle();
+    let pid = Pid::from_raw(child.id() as i32);
+    let forwarder = thread::spawn(move || {
+        for caught in signals.forever() {
+            if let Ok(caught) = Signal::try_from(caught) {
+                log::info!("Forwarding {} to pid {}", caught, pid);
+                let _ = signal::kill(pid, caught);
+            }
+        }
+    });
+    if let Ok(caught) = Signal::try_from(pending.swap(0, Ordering::SeqCst) as i32) {
+        log::info!(
+            "Passing on {}, which came while pid {} started",
+            caught,
+            pid
+        );
+        let _ = signal::kill(pid, caught);
+    }
+    let status = child.wait();
+    handle.close();
+    let _ = forwarder.join();
+    status
+}
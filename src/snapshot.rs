@@ -0,0 +1,258 @@
+use crate::clock;
+use crate::details;
+use crate::locale;
+use crate::sessions;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use std::{fs, io};
+
+/// What `list --json` prints, and `diff` compares against
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Snapshot {
+    /// When it was taken, in Unix seconds
+    pub taken: u64,
+    /// Whether exited sessions were listed too
+    pub include_dead: bool,
+    pub sessions: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub status: Status,
+    pub attached: bool,
+    /// When the server started (or the session was saved, once exited)
+    pub created: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Running,
+    Exited,
+}
+
+impl Snapshot {
+    pub fn take(socket_dir: &Path, running: &[String], dead: Option<&[String]>) -> Self {
+        let entry = |name: &String, status| Entry {
+            name: name.clone(),
+            status,
+            attached: status == Status::Running && sessions::is_attached(socket_dir, name),
+            created: sessions::created_at(socket_dir, name)
+                .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+                .map(|at| at.as_secs()),
+        };
+        let mut entries: Vec<Entry> = running.iter().map(|s| entry(s, Status::Running)).collect();
+        entries.extend(
+            dead.unwrap_or_default()
+                .iter()
+                .map(|s| entry(s, Status::Exited)),
+        );
+        Snapshot {
+            taken: clock::now(),
+            include_dead: dead.is_some(),
+            sessions: entries,
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let raw = fs::read(path)?;
+        serde_json::from_slice(&raw).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        })
+    }
+
+    fn get(&self, name: &str) -> Option<&Entry> {
+        self.sessions.iter().find(|entry| entry.name == name)
+    }
+}
+
+/// What happened to the sessions between two snapshots
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub created: Vec<String>,
+    pub killed: Vec<String>,
+    /// Running in both, under a new name. Renaming keeps the socket's
+    /// timestamp, so a session that vanished and one that appeared with
+    /// the same start time are taken to be the same.
+    pub renamed: Vec<(String, String)>,
+    pub exited: Vec<String>,
+    /// Exited before, resurrected since
+    pub revived: Vec<String>,
+    /// Exited before, and their saved state deleted since
+    pub purged: Vec<String>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.killed.is_empty()
+            && self.renamed.is_empty()
+            && self.exited.is_empty()
+            && self.revived.is_empty()
+            && self.purged.is_empty()
+    }
+}
+
+pub fn diff(before: &Snapshot, now: &Snapshot) -> Changes {
+    let mut changes = Changes::default();
+    let mut appeared: Vec<&Entry> = Vec::new();
+    for entry in &now.sessions {
+        match before.get(&entry.name).map(|old| old.status) {
+            None => appeared.push(entry),
+            Some(Status::Running) if entry.status == Status::Exited => {
+                changes.exited.push(entry.name.clone())
+            }
+            Some(Status::Exited) if entry.status == Status::Running => {
+                changes.revived.push(entry.name.clone())
+            }
+            Some(_) => (),
+        }
+    }
+    for old in &before.sessions {
+        if now.get(&old.name).is_some() {
+            continue;
+        }
+        let renamed = appeared.iter().position(|new| {
+            old.status == Status::Running
+                && new.status == Status::Running
+                && old.created.is_some()
+                && new.created == old.created
+        });
+        match renamed {
+            Some(idx) => {
+                let new = appeared.remove(idx);
+                changes.renamed.push((old.name.clone(), new.name.clone()));
+            }
+            None if old.status == Status::Exited => changes.purged.push(old.name.clone()),
+            None => changes.killed.push(old.name.clone()),
+        }
+    }
+    changes.created = appeared.into_iter().map(|e| e.name.clone()).collect();
+    changes
+}
+
+/// The changes as a list for people, headed by when `before` was taken
+pub fn render(before: &Snapshot, changes: &Changes, now: u64) -> String {
+    let mut out = format!(
+        "Since {} ({}):",
+        locale::current().datetime(before.taken),
+        details::ago(before.taken, now)
+    );
+    if changes.is_empty() {
+        out.push_str("\n  nothing changed");
+        return out;
+    }
+    for name in &changes.created {
+        out.push_str(&format!("\n  + {} created", name));
+    }
+    for name in &changes.killed {
+        out.push_str(&format!("\n  - {} killed", name));
+    }
+    for (from, to) in &changes.renamed {
+        out.push_str(&format!("\n  ~ {} renamed to {}", from, to));
+    }
+    for name in &changes.exited {
+        out.push_str(&format!("\n  x {} exited (resurrectable)", name));
+    }
+    for name in &changes.revived {
+        out.push_str(&format!("\n  ^ {} resurrected", name));
+    }
+    for name in &changes.purged {
+        out.push_str(&format!("\n  - {} purged", name));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Status::{Exited, Running};
+
+    fn snapshot(sessions: &[(&str, Status, Option<u64>)]) -> Snapshot {
+        Snapshot {
+            taken: 0,
+            include_dead: true,
+            sessions: sessions
+                .iter()
+                .map(|&(name, status, created)| Entry {
+                    name: name.to_string(),
+                    status,
+                    attached: false,
+                    created,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn nothing_changed() {
+        let sessions = [("api", Running, Some(10)), ("old", Exited, Some(5))];
+        assert!(diff(&snapshot(&sessions), &snapshot(&sessions)).is_empty());
+    }
+
+    #[test]
+    fn sessions_come_and_go() {
+        let before = snapshot(&[("api", Running, Some(10)), ("web", Running, Some(20))]);
+        let now = snapshot(&[("api", Running, Some(10)), ("db", Running, Some(30))]);
+        let changes = diff(&before, &now);
+        assert_eq!(changes.created, ["db"]);
+        assert_eq!(changes.killed, ["web"]);
+        assert!(changes.renamed.is_empty());
+    }
+
+    #[test]
+    fn same_start_time_under_a_new_name_is_a_rename() {
+        let before = snapshot(&[("api", Running, Some(10))]);
+        let now = snapshot(&[("backend", Running, Some(10))]);
+        let changes = diff(&before, &now);
+        assert_eq!(
+            changes.renamed,
+            [("api".to_string(), "backend".to_string())]
+        );
+        assert!(changes.created.is_empty());
+        assert!(changes.killed.is_empty());
+    }
+
+    #[test]
+    fn renames_leave_unrelated_sessions_alone() {
+        let before = snapshot(&[("api", Running, Some(10)), ("web", Running, Some(20))]);
+        let now = snapshot(&[
+            ("scratch", Running, Some(40)),
+            ("backend", Running, Some(10)),
+            ("web", Running, Some(20)),
+        ]);
+        let changes = diff(&before, &now);
+        assert_eq!(
+            changes.renamed,
+            [("api".to_string(), "backend".to_string())]
+        );
+        assert_eq!(changes.created, ["scratch"]);
+        assert!(changes.killed.is_empty());
+    }
+
+    #[test]
+    fn renames_need_a_known_start_time_and_a_running_session() {
+        let before = snapshot(&[("api", Running, None), ("old", Exited, Some(5))]);
+        let now = snapshot(&[("backend", Running, None), ("new", Running, Some(5))]);
+        let changes = diff(&before, &now);
+        assert!(changes.renamed.is_empty());
+        assert_eq!(changes.created, ["backend", "new"]);
+        assert_eq!(changes.killed, ["api"]);
+        assert_eq!(changes.purged, ["old"]);
+    }
+
+    #[test]
+    fn exits_and_resurrections() {
+        let before = snapshot(&[("api", Running, Some(10)), ("old", Exited, Some(5))]);
+        let now = snapshot(&[("api", Exited, Some(10)), ("old", Running, Some(50))]);
+        let changes = diff(&before, &now);
+        assert_eq!(changes.exited, ["api"]);
+        assert_eq!(changes.revived, ["old"]);
+        assert!(changes.created.is_empty() && changes.killed.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::config::{Config, Template};
+use crate::listing::{GroupBy, SortKey};
+use crate::store;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+
+// Older attaches than this many are forgotten, they'd barely move frecency anyway
+const MAX_ATTACHES: usize = 50;
+const MAX_QUERIES: usize = 100;
+
+/// What the chooser remembers between runs, kept in
+/// `$XDG_STATE_HOME/zellij-chooser/` by whichever store is configured
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct State {
+    pub sessions: BTreeMap<String, SessionRecord>,
+    /// Filter queries typed into the picker, most recent last
+    pub queries: Vec<String>,
+    /// When the picker last pointed out idle sessions
+    pub idle_reminded: Option<u64>,
+    /// Named sets of sessions saved with `set save`, used as `@name`
+    pub sets: BTreeMap<String, Vec<String>>,
+    /// How the picker sorts and groups without --sort or --group-by, as
+    /// last chosen in its sort menu
+    pub sort: Option<SortKey>,
+    pub group_by: Option<GroupBy>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct SessionRecord {
+    pub tags: Vec<String>,
+    pub notes: String,
+    pub cwd: Option<PathBuf>,
+    /// Unix timestamps of the most recent attaches, oldest first
+    pub attaches: Vec<u64>,
+    /// The template it was started from, for its hooks
+    pub template: Option<String>,
+    /// Attaches all told, including the ones too old to be in `attaches`
+    /// (see `times_attached`)
+    pub attach_count: u64,
+    /// Seconds spent attached all told, counted once the client exits
+    pub attached_secs: u64,
+}
+
+impl SessionRecord {
+    /// How many times it's been attached to; state from before the count
+    /// was kept only has the recent attaches to go by
+    pub fn times_attached(&self) -> u64 {
+        self.attach_count.max(self.attaches.len() as u64)
+    }
+
+    /// Firefox-style frecency: every remembered attach is worth more the
+    /// more recent it is
+    pub fn frecency(&self, now: u64) -> f64 {
+        self.attaches
+            .iter()
+            .map(|at| match now.saturating_sub(*at) {
+                age if age < 4 * 3600 => 100.0,
+                age if age < 24 * 3600 => 70.0,
+                age if age < 7 * 24 * 3600 => 50.0,
+                age if age < 30 * 24 * 3600 => 30.0,
+                _ => 10.0,
+            })
+            .sum()
+    }
+}
+
+pub fn state_dir() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("zellij-chooser"))
+}
+
+/// For what means nothing after a reboot (locks, what's refreshed on
+/// attach); the state directory where there's no runtime directory
+pub fn runtime_dir() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .map(|dir| dir.join("zellij-chooser"))
+        .or_else(state_dir)
+}
+
+impl State {
+    /// Missing state is simply empty state
+    pub fn load() -> io::Result<Self> {
+        store::current().load()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        store::current().save(self)
+    }
+
+    /// Starts keeping track of a session started some other way, with the
+    /// directory it's about (unless one was recorded already), tags and
+    /// sets to be in
+    pub fn adopt(&mut self, session: &str, cwd: Option<PathBuf>, tags: &[String], sets: &[String]) {
+        let record = self.sessions.entry(session.to_string()).or_default();
+        if record.cwd.is_none() {
+            record.cwd = cwd;
+        }
+        for tag in tags {
+            if !record.tags.contains(tag) {
+                record.tags.push(tag.clone());
+            }
+        }
+        for set in sets {
+            let members = self.sets.entry(set.clone()).or_default();
+            if !members.iter().any(|member| member == session) {
+                members.push(session.to_string());
+            }
+        }
+    }
+
+    /// The template `session` was started from, if we were the ones to
+    /// start it and it's still in the config
+    pub fn template_of<'c>(&self, config: &'c Config, session: &str) -> Option<&'c Template> {
+        self.sessions
+            .get(session)
+            .and_then(|record| record.template.as_deref())
+            .and_then(|name| config.templates.get(name))
+    }
+
+    /// Remembers which template a session we started came from
+    pub fn record_template(&mut self, session: &str, template: &Template) {
+        self.sessions
+            .entry(session.to_string())
+            .or_default()
+            .template = template.name.clone();
+    }
+
+    pub fn record_attach(&mut self, session: &str, at: u64) {
+        let record = self.sessions.entry(session.to_string()).or_default();
+        record.attach_count = record.times_attached() + 1;
+        record.attaches.push(at);
+        if record.attaches.len() > MAX_ATTACHES {
+            let excess = record.attaches.len() - MAX_ATTACHES;
+            record.attaches.drain(..excess);
+        }
+    }
+
+    pub fn record_time_attached(&mut self, session: &str, secs: u64) {
+        let record = self.sessions.entry(session.to_string()).or_default();
+        record.attached_secs += secs;
+    }
+
+    pub fn record_query(&mut self, query: &str) {
+        self.queries.retain(|q| q != query);
+        self.queries.push(query.to_string());
+        if self.queries.len() > MAX_QUERIES {
+            let excess = self.queries.len() - MAX_QUERIES;
+            self.queries.drain(..excess);
+        }
+    }
+
+    /// Session names with every `@set` replaced by its members (or a
+    /// group's, when there's no such set), or the name of the first that's
+    /// neither
+    pub fn expand_sets(
+        &self,
+        names: &[String],
+        groups: &BTreeMap<String, Vec<String>>,
+    ) -> Result<Vec<String>, String> {
+        let mut expanded: Vec<String> = Vec::new();
+        for name in names {
+            let members = match name.strip_prefix('@') {
+                Some(set) => self
+                    .sets
+                    .get(set)
+                    .or_else(|| groups.get(set))
+                    .ok_or_else(|| set.to_string())?
+                    .clone(),
+                None => vec![name.clone()],
+            };
+            for member in members {
+                if !expanded.contains(&member) {
+                    expanded.push(member);
+                }
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Moves everything known about `from` over to `to`, merging with
+    /// whatever `to` already had
+    pub fn rename_session(&mut self, from: &str, to: &str) {
+        for members in self.sets.values_mut() {
+            for member in members.iter_mut().filter(|member| *member == from) {
+                *member = to.to_string();
+            }
+        }
+        let Some(old) = self.sessions.remove(from) else {
+            return;
+        };
+        let old_count = old.times_attached();
+        let record = self.sessions.entry(to.to_string()).or_default();
+        for tag in old.tags {
+            if !record.tags.contains(&tag) {
+                record.tags.push(tag);
+            }
+        }
+        if record.notes.is_empty() {
+            record.notes = old.notes;
+        }
+        if record.cwd.is_none() {
+            record.cwd = old.cwd;
+        }
+        if record.template.is_none() {
+            record.template = old.template;
+        }
+        record.attach_count = record.times_attached() + old_count;
+        record.attached_secs += old.attached_secs;
+        record.attaches.extend(old.attaches);
+        record.attaches.sort_unstable();
+        if record.attaches.len() > MAX_ATTACHES {
+            let excess = record.attaches.len() - MAX_ATTACHES;
+            record.attaches.drain(..excess);
+        }
+    }
+}
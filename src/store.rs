@@ -0,0 +1,361 @@
+use crate::config::StateBackend;
+use crate::state::{state_dir, State};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::{fs, io};
+
+/// Somewhere the state is kept between runs
+pub trait Store: Send + Sync {
+    /// Missing state is simply empty state
+    fn load(&self) -> io::Result<State>;
+    fn save(&self, state: &State) -> io::Result<()>;
+    /// For a forked process to call before it loads or saves: whatever
+    /// was opened before the fork isn't to be used after it
+    fn reopen(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The whole state as JSON, in `state.json`, rewritten on every save
+pub(crate) struct FileStore {
+    path: Option<PathBuf>,
+}
+
+impl FileStore {
+    pub fn new() -> Self {
+        FileStore {
+            path: state_dir().map(|dir| dir.join("state.json")),
+        }
+    }
+}
+
+impl Store for FileStore {
+    fn load(&self) -> io::Result<State> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(State::default()),
+        };
+        match fs::read(path) {
+            Ok(raw) => serde_json::from_slice(&raw).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", path.display(), err),
+                )
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(State::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Written to a sibling file and renamed over, so a crash mid-write
+    // can't leave a truncated state behind
+    fn save(&self, state: &State) -> io::Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        let raw = serde_json::to_vec_pretty(state).map_err(io::Error::other)?;
+        fs::write(&tmp, raw)?;
+        fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::{FileStore, Store};
+    use crate::state::{state_dir, SessionRecord, State};
+    use rusqlite::{params, Connection, OptionalExtension};
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+    use std::{fs, io};
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS sessions (
+            name TEXT PRIMARY KEY,
+            tags TEXT NOT NULL,
+            notes TEXT NOT NULL,
+            cwd TEXT,
+            template TEXT
+        );
+        CREATE TABLE IF NOT EXISTS attaches (
+            session TEXT NOT NULL,
+            at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS attaches_by_session ON attaches (session);
+        CREATE TABLE IF NOT EXISTS stats (
+            session TEXT PRIMARY KEY,
+            attach_count INTEGER NOT NULL,
+            attached_secs INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS queries (
+            position INTEGER PRIMARY KEY,
+            query TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+    ";
+
+    fn failed(err: rusqlite::Error) -> io::Error {
+        io::Error::other(format!("state.sqlite: {}", err))
+    }
+
+    fn json<T: serde::Serialize>(value: &T) -> io::Result<String> {
+        serde_json::to_string(value).map_err(io::Error::other)
+    }
+
+    fn unjson<T: serde::de::DeserializeOwned>(raw: &str) -> rusqlite::Result<T> {
+        serde_json::from_str(raw).map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, err.into())
+        })
+    }
+
+    /// The state in `state.sqlite`, where a save only writes what changed
+    /// since the load, rather than every session's whole history
+    pub struct SqliteStore {
+        path: PathBuf,
+        connection: Mutex<Connection>,
+        // What's in the database, as of the last load or save
+        stored: Mutex<State>,
+    }
+
+    impl SqliteStore {
+        pub fn open() -> io::Result<Self> {
+            let dir = state_dir()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state directory"))?;
+            fs::create_dir_all(&dir)?;
+            let path: PathBuf = dir.join("state.sqlite");
+            let fresh = !path.exists();
+            let connection = Connection::open(&path).map_err(failed)?;
+            connection.execute_batch(SCHEMA).map_err(failed)?;
+            let store = SqliteStore {
+                path,
+                connection: Mutex::new(connection),
+                stored: Mutex::new(State::default()),
+            };
+            // Carry over what was kept in state.json so far
+            if fresh {
+                let state = FileStore::new().load()?;
+                store.save(&state)?;
+            }
+            Ok(store)
+        }
+
+        fn read(connection: &Connection) -> rusqlite::Result<State> {
+            let mut state = State::default();
+            let mut sessions =
+                connection.prepare("SELECT name, tags, notes, cwd, template FROM sessions")?;
+            let rows = sessions.query_map([], |row| {
+                let tags: String = row.get(1)?;
+                let cwd: Option<String> = row.get(3)?;
+                Ok((
+                    row.get::<_, String>(0)?,
+                    SessionRecord {
+                        tags: unjson(&tags)?,
+                        notes: row.get(2)?,
+                        cwd: cwd.map(PathBuf::from),
+                        attaches: Vec::new(),
+                        template: row.get(4)?,
+                        ..SessionRecord::default()
+                    },
+                ))
+            })?;
+            for row in rows {
+                let (name, record) = row?;
+                state.sessions.insert(name, record);
+            }
+            let mut attaches =
+                connection.prepare("SELECT session, at FROM attaches ORDER BY session, at")?;
+            let rows = attaches.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?;
+            for row in rows {
+                let (session, at) = row?;
+                if let Some(record) = state.sessions.get_mut(&session) {
+                    record.attaches.push(at as u64);
+                }
+            }
+            let mut stats =
+                connection.prepare("SELECT session, attach_count, attached_secs FROM stats")?;
+            let rows = stats.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })?;
+            for row in rows {
+                let (session, count, secs) = row?;
+                if let Some(record) = state.sessions.get_mut(&session) {
+                    record.attach_count = count as u64;
+                    record.attached_secs = secs as u64;
+                }
+            }
+            let mut queries = connection.prepare("SELECT query FROM queries ORDER BY position")?;
+            state.queries = queries
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+            let setting = |key: &str| -> rusqlite::Result<Option<String>> {
+                connection
+                    .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+                        row.get(0)
+                    })
+                    .optional()
+            };
+            if let Some(raw) = setting("idle_reminded")? {
+                state.idle_reminded = unjson(&raw)?;
+            }
+            if let Some(raw) = setting("sets")? {
+                state.sets = unjson(&raw)?;
+            }
+            if let Some(raw) = setting("sort")? {
+                state.sort = unjson(&raw)?;
+            }
+            if let Some(raw) = setting("group_by")? {
+                state.group_by = unjson(&raw)?;
+            }
+            Ok(state)
+        }
+    }
+
+    impl Store for SqliteStore {
+        fn load(&self) -> io::Result<State> {
+            let connection = self.connection.lock().unwrap();
+            let state = Self::read(&connection).map_err(failed)?;
+            *self.stored.lock().unwrap() = state.clone();
+            Ok(state)
+        }
+
+        fn save(&self, state: &State) -> io::Result<()> {
+            let mut connection = self.connection.lock().unwrap();
+            let mut stored = self.stored.lock().unwrap();
+            let tx = connection.transaction().map_err(failed)?;
+            let unchanged =
+                |name: &String, record: &SessionRecord| stored.sessions.get(name) == Some(record);
+            for (name, record) in &state.sessions {
+                if unchanged(name, record) {
+                    continue;
+                }
+                tx.execute(
+                    "INSERT OR REPLACE INTO sessions (name, tags, notes, cwd, template)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        name,
+                        json(&record.tags)?,
+                        record.notes,
+                        record.cwd.as_ref().map(|cwd| cwd.to_string_lossy()),
+                        record.template
+                    ],
+                )
+                .map_err(failed)?;
+                tx.execute(
+                    "INSERT OR REPLACE INTO stats (session, attach_count, attached_secs)
+                     VALUES (?1, ?2, ?3)",
+                    params![
+                        name,
+                        record.attach_count as i64,
+                        record.attached_secs as i64
+                    ],
+                )
+                .map_err(failed)?;
+                tx.execute("DELETE FROM attaches WHERE session = ?1", [name])
+                    .map_err(failed)?;
+                for at in &record.attaches {
+                    tx.execute(
+                        "INSERT INTO attaches (session, at) VALUES (?1, ?2)",
+                        params![name, *at as i64],
+                    )
+                    .map_err(failed)?;
+                }
+            }
+            for name in stored.sessions.keys() {
+                if !state.sessions.contains_key(name) {
+                    tx.execute("DELETE FROM sessions WHERE name = ?1", [name])
+                        .map_err(failed)?;
+                    tx.execute("DELETE FROM attaches WHERE session = ?1", [name])
+                        .map_err(failed)?;
+                    tx.execute("DELETE FROM stats WHERE session = ?1", [name])
+                        .map_err(failed)?;
+                }
+            }
+            if state.queries != stored.queries {
+                tx.execute("DELETE FROM queries", []).map_err(failed)?;
+                for (position, query) in state.queries.iter().enumerate() {
+                    tx.execute(
+                        "INSERT INTO queries (position, query) VALUES (?1, ?2)",
+                        params![position as i64, query],
+                    )
+                    .map_err(failed)?;
+                }
+            }
+            let settings: BTreeMap<&str, String> = [
+                ("idle_reminded", json(&state.idle_reminded)?),
+                ("sets", json(&state.sets)?),
+                ("sort", json(&state.sort)?),
+                ("group_by", json(&state.group_by)?),
+            ]
+            .into_iter()
+            .collect();
+            for (key, value) in settings {
+                tx.execute(
+                    "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                    params![key, value],
+                )
+                .map_err(failed)?;
+            }
+            tx.commit().map_err(failed)?;
+            *stored = state.clone();
+            Ok(())
+        }
+
+        // SQLite mustn't so much as close a connection carried across a
+        // fork, so the old one is left be
+        fn reopen(&self) -> io::Result<()> {
+            let connection = Connection::open(&self.path).map_err(failed)?;
+            std::mem::forget(std::mem::replace(
+                &mut *self.connection.lock().unwrap(),
+                connection,
+            ));
+            Ok(())
+        }
+    }
+}
+
+static CURRENT: OnceLock<Box<dyn Store>> = OnceLock::new();
+
+/// Settles where the state is kept for the rest of the run
+pub fn init(backend: StateBackend) {
+    let store: Box<dyn Store> = match backend {
+        StateBackend::File => Box::new(FileStore::new()),
+        #[cfg(feature = "sqlite")]
+        StateBackend::Sqlite => match SqliteStore::open() {
+            Ok(store) => Box::new(store),
+            Err(err) => {
+                eprintln!("Couldn't open state.sqlite, using state.json: {}", err);
+                Box::new(FileStore::new())
+            }
+        },
+        #[cfg(not(feature = "sqlite"))]
+        StateBackend::Sqlite => {
+            eprintln!(
+                "This build of zellij-chooser has no sqlite support (the `sqlite` feature), \
+                 using state.json"
+            );
+            Box::new(FileStore::new())
+        }
+    };
+    let _ = CURRENT.set(store);
+}
+
+pub fn current() -> &'static dyn Store {
+    CURRENT.get_or_init(|| Box::new(FileStore::new())).as_ref()
+}
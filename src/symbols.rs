@@ -0,0 +1,120 @@
+use crate::config::SymbolMode;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+/// The glyphs we draw with, so terminals that can't show Unicode get
+/// something legible instead of tofu
+#[derive(Debug)]
+pub struct Symbols {
+    pub box_top: &'static str,
+    pub box_side: &'static str,
+    pub box_bottom: &'static str,
+    pub box_rule: &'static str,
+    /// Marks the focused tab in a preview
+    pub focus: &'static str,
+    /// Marks the session Enter would pick
+    pub selected: &'static str,
+    pub attached: &'static str,
+    pub detached: &'static str,
+    pub dead: &'static str,
+    pub spinner: &'static [&'static str],
+}
+
+impl Symbols {
+    pub const UNICODE: Symbols = Symbols {
+        box_top: "┌",
+        box_side: "│",
+        box_bottom: "└",
+        box_rule: "─",
+        focus: "●",
+        selected: "▸",
+        attached: "●",
+        detached: "○",
+        dead: "✗",
+        spinner: &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+    };
+
+    pub const ASCII: Symbols = Symbols {
+        box_top: "+",
+        box_side: "|",
+        box_bottom: "+",
+        box_rule: "-",
+        focus: "*",
+        selected: ">",
+        attached: "*",
+        detached: "o",
+        dead: "x",
+        spinner: &["|", "/", "-", "\\"],
+    };
+}
+
+static CURRENT: OnceLock<&'static Symbols> = OnceLock::new();
+
+// Consoles that are known not to have the glyphs, whatever the locale says
+const ASCII_TERMS: [&str; 5] = ["dumb", "linux", "vt100", "vt220", "cons25"];
+
+// Guesses from $TERM and the locale (the first of LC_ALL, LC_CTYPE and LANG
+// that's set, like libc does) whether Unicode will come out right
+fn unicode_capable() -> bool {
+    let term = env::var("TERM").unwrap_or_default();
+    if ASCII_TERMS.contains(&term.as_str()) {
+        return false;
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+/// Settles which symbols to use for the rest of the run
+pub fn init(mode: SymbolMode) {
+    let symbols = match mode {
+        SymbolMode::Unicode => &Symbols::UNICODE,
+        SymbolMode::Ascii => &Symbols::ASCII,
+        SymbolMode::Auto if unicode_capable() => &Symbols::UNICODE,
+        SymbolMode::Auto => &Symbols::ASCII,
+    };
+    let _ = CURRENT.set(symbols);
+}
+
+pub fn current() -> &'static Symbols {
+    CURRENT.get().copied().unwrap_or(&Symbols::ASCII)
+}
+
+const SPINNER_TICK: Duration = Duration::from_millis(80);
+
+/// Runs `work`, spinning next to `message` on stderr while it does (unless
+/// stderr isn't a terminal, where it would just be noise)
+pub fn spin<T>(message: &str, work: impl FnOnce() -> T) -> T {
+    if !io::stderr().is_terminal() {
+        return work();
+    }
+    let done = AtomicBool::new(false);
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            let frames = current().spinner;
+            let mut stderr = io::stderr();
+            for frame in frames.iter().cycle() {
+                if done.load(Ordering::Relaxed) {
+                    break;
+                }
+                let _ = write!(stderr, "\r{} {}", frame, message);
+                let _ = stderr.flush();
+                thread::sleep(SPINNER_TICK);
+            }
+            // Wipe the line so whatever comes next starts clean
+            let _ = write!(stderr, "\r{}\r", " ".repeat(message.chars().count() + 2));
+            let _ = stderr.flush();
+        });
+        let result = work();
+        done.store(true, Ordering::Relaxed);
+        result
+    })
+}
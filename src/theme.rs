@@ -0,0 +1,229 @@
+use crate::config::{ThemeBase, ThemeConfig};
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::fmt;
+
+// WCAG AA asks for this much contrast for normal-sized text
+const MIN_CONTRAST: f64 = 4.5;
+// CIE76 distance below which two status colors are too easy to confuse
+const MIN_DISTINCT: f64 = 20.0;
+
+/// A 24-bit color, written `#rrggbb` (or `#rgb`) in the config
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        let hex = raw.strip_prefix('#').unwrap_or(&raw);
+        let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+        let parsed = match hex.len() {
+            6 => channel(&hex[0..2])
+                .zip(channel(&hex[2..4]))
+                .zip(channel(&hex[4..6]))
+                .map(|((r, g), b)| Rgb(r, g, b)),
+            3 => channel(&hex[0..1])
+                .zip(channel(&hex[1..2]))
+                .zip(channel(&hex[2..3]))
+                .map(|((r, g), b)| Rgb(r * 17, g * 17, b * 17)),
+            _ => None,
+        };
+        parsed.ok_or_else(|| format!("\"{}\" isn't a #rrggbb color", raw))
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+fn to_linear(channel: u8) -> f64 {
+    let c = channel as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(value: f64) -> u8 {
+    let v = value.clamp(0.0, 1.0);
+    let c = if v <= 0.003_130_8 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
+
+// Machado, Oliveira & Fernandes (2009) at full severity, in linear RGB
+const PROTANOPIA: [[f64; 3]; 3] = [
+    [0.152_286, 1.052_583, -0.204_868],
+    [0.114_503, 0.786_281, 0.099_216],
+    [-0.003_882, -0.048_116, 1.051_998],
+];
+const DEUTERANOPIA: [[f64; 3]; 3] = [
+    [0.367_322, 0.860_646, -0.227_968],
+    [0.280_085, 0.672_501, 0.047_413],
+    [-0.011_820, 0.042_940, 0.968_881],
+];
+const TRITANOPIA: [[f64; 3]; 3] = [
+    [1.255_528, -0.076_749, -0.178_779],
+    [-0.078_411, 0.930_809, 0.147_602],
+    [0.004_733, 0.691_367, 0.303_900],
+];
+const DEFICIENCIES: [(&str, [[f64; 3]; 3]); 3] = [
+    ("protanopia", PROTANOPIA),
+    ("deuteranopia", DEUTERANOPIA),
+    ("tritanopia", TRITANOPIA),
+];
+
+impl Rgb {
+    fn linear(&self) -> [f64; 3] {
+        [to_linear(self.0), to_linear(self.1), to_linear(self.2)]
+    }
+
+    fn luminance(&self) -> f64 {
+        let [r, g, b] = self.linear();
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+
+    /// WCAG contrast ratio, from 1 (none) to 21 (black on white)
+    pub fn contrast(&self, other: &Rgb) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    // How this color looks to someone with the given deficiency
+    fn simulate(&self, matrix: &[[f64; 3]; 3]) -> Rgb {
+        let lin = self.linear();
+        let row = |r: &[f64; 3]| from_linear(r[0] * lin[0] + r[1] * lin[1] + r[2] * lin[2]);
+        Rgb(row(&matrix[0]), row(&matrix[1]), row(&matrix[2]))
+    }
+
+    fn lab(&self) -> [f64; 3] {
+        let [r, g, b] = self.linear();
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.950_47;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.088_83;
+        let f = |t: f64| {
+            if t > 0.008_856 {
+                t.cbrt()
+            } else {
+                7.787 * t + 16.0 / 116.0
+            }
+        };
+        [
+            116.0 * f(y) - 16.0,
+            500.0 * (f(x) - f(y)),
+            200.0 * (f(y) - f(z)),
+        ]
+    }
+
+    fn distance(&self, other: &Rgb) -> f64 {
+        let (a, b) = (self.lab(), other.lab());
+        ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+    }
+}
+
+/// The colors the chooser draws with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Only used to judge contrast, we never paint the background
+    pub background: Rgb,
+    pub accent: Rgb,
+    pub attached: Rgb,
+    pub detached: Rgb,
+    pub dead: Rgb,
+}
+
+impl Theme {
+    pub const DEFAULT: Theme = Theme {
+        background: Rgb(0x00, 0x00, 0x00),
+        accent: Rgb(0x5f, 0xd7, 0xff),
+        attached: Rgb(0x87, 0xd7, 0x5f),
+        detached: Rgb(0xd7, 0xaf, 0x5f),
+        dead: Rgb(0x80, 0x80, 0x80),
+    };
+
+    /// Okabe & Ito's palette, which stays distinguishable under protanopia,
+    /// deuteranopia and tritanopia (unlike the default green/amber pair)
+    pub const COLORBLIND: Theme = Theme {
+        background: Rgb(0x00, 0x00, 0x00),
+        accent: Rgb(0xf0, 0xe4, 0x42),
+        attached: Rgb(0x56, 0xb4, 0xe9),
+        detached: Rgb(0xe6, 0x9f, 0x00),
+        dead: Rgb(0x99, 0x99, 0x99),
+    };
+
+    pub fn from_config(config: &ThemeConfig) -> Self {
+        let base = match config.base {
+            ThemeBase::Default => Self::DEFAULT,
+            ThemeBase::Colorblind => Self::COLORBLIND,
+        };
+        Self {
+            background: config.background.unwrap_or(base.background),
+            accent: config.accent.unwrap_or(base.accent),
+            attached: config.attached.unwrap_or(base.attached),
+            detached: config.detached.unwrap_or(base.detached),
+            dead: config.dead.unwrap_or(base.dead),
+        }
+    }
+
+    fn roles(&self) -> [(&'static str, Rgb); 4] {
+        [
+            ("accent", self.accent),
+            ("attached", self.attached),
+            ("detached", self.detached),
+            ("dead", self.dead),
+        ]
+    }
+
+    /// Complaints about colors that will be hard to read on the background,
+    /// plus, for the colorblind theme, status colors that collapse into each
+    /// other under a common color-vision deficiency
+    pub fn lint(&self, colorblind_safe: bool) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (role, color) in self.roles() {
+            let contrast = color.contrast(&self.background);
+            if contrast < MIN_CONTRAST {
+                warnings.push(format!(
+                    "theme.{} ({}) only has a {:.1}:1 contrast against the {} background, \
+                     {:.1}:1 is recommended",
+                    role, color, contrast, self.background, MIN_CONTRAST
+                ));
+            }
+        }
+        if colorblind_safe {
+            let statuses = [
+                ("attached", self.attached),
+                ("detached", self.detached),
+                ("dead", self.dead),
+            ];
+            for (i, (a_role, a)) in statuses.iter().enumerate() {
+                for (b_role, b) in &statuses[i + 1..] {
+                    for (deficiency, matrix) in &DEFICIENCIES {
+                        if a.simulate(matrix).distance(&b.simulate(matrix)) < MIN_DISTINCT {
+                            warnings.push(format!(
+                                "theme.{} and theme.{} look alike with {}",
+                                a_role, b_role, deficiency
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        warnings
+    }
+}
+
+/// Wraps `text` in a 24-bit foreground color escape
+pub fn paint(text: &str, color: Rgb) -> String {
+    format!(
+        "\x1b[38;2;{};{};{}m{}\x1b[0m",
+        color.0, color.1, color.2, text
+    )
+}
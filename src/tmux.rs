@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::io;
+use std::process::{Command, Stdio};
+
+// Panes running one of these are just shells, and start as plain panes
+const SHELLS: [&str; 7] = ["bash", "zsh", "fish", "sh", "dash", "ksh", "tcsh"];
+
+/// A tmux session, as much of it as carries over to zellij
+#[derive(Debug)]
+pub struct Session {
+    pub windows: Vec<Window>,
+}
+
+#[derive(Debug)]
+pub struct Window {
+    pub name: String,
+    pub active: bool,
+    pub layout: Layout,
+}
+
+/// How a window is split up. tmux sizes are in cells; zellij gets
+/// percentages of the parent.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Layout {
+    Pane(Pane),
+    /// Children side by side (`vertical`) or stacked, with their sizes
+    /// along that axis
+    Split {
+        vertical: bool,
+        children: Vec<(u32, Layout)>,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pane {
+    pub cwd: Option<String>,
+    /// What it was running, unless that was a shell
+    pub command: Option<String>,
+}
+
+fn tmux(args: &[&str]) -> io::Result<String> {
+    log::debug!("Running tmux {}", args.join(" "));
+    let output = Command::new("tmux")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "`tmux {}` exited with {}: {}",
+            args[0],
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The names of the running tmux sessions
+pub fn session_names() -> io::Result<Vec<String>> {
+    Ok(tmux(&["list-sessions", "-F", "#{session_name}"])?
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Reads a session's windows and panes out of tmux
+pub fn read_session(name: &str) -> io::Result<Session> {
+    // `=` so tmux doesn't take the name as a prefix
+    let target = format!("={}", name);
+    let panes: HashMap<String, Pane> = tmux(&[
+        "list-panes",
+        "-s",
+        "-t",
+        &target,
+        "-F",
+        "#{pane_id}\t#{pane_current_path}\t#{pane_current_command}",
+    ])?
+    .lines()
+    .filter_map(|line| {
+        let mut fields = line.splitn(3, '\t');
+        let id = fields.next()?.trim_start_matches('%').to_string();
+        let cwd = fields
+            .next()
+            .filter(|cwd| !cwd.is_empty())
+            .map(str::to_string);
+        let command = fields
+            .next()
+            .filter(|cmd| !cmd.is_empty() && !SHELLS.contains(cmd))
+            .map(str::to_string);
+        Some((id, Pane { cwd, command }))
+    })
+    .collect();
+    let windows = tmux(&[
+        "list-windows",
+        "-t",
+        &target,
+        "-F",
+        "#{window_active}\t#{window_layout}\t#{window_name}",
+    ])?
+    .lines()
+    .filter_map(|line| {
+        let mut fields = line.splitn(3, '\t');
+        let active = fields.next()? == "1";
+        let layout = parse_layout(fields.next()?, &panes)?;
+        let name = fields.next().unwrap_or_default().to_string();
+        Some(Window {
+            name,
+            active,
+            layout,
+        })
+    })
+    .collect();
+    Ok(Session { windows })
+}
+
+/// Makes sense of a `#{window_layout}` like
+/// `b25d,159x48,0,0{79x48,0,0,1,79x48,80,0[79x24,80,0,2,79x23,80,25,3]}`:
+/// a checksum, then nested cells, each `WxH,X,Y` followed by a pane id or a
+/// `{}` (side by side) or `[]` (stacked) list of cells
+fn parse_layout(layout: &str, panes: &HashMap<String, Pane>) -> Option<Layout> {
+    let (_checksum, cells) = layout.split_once(',')?;
+    let mut rest = cells;
+    let (_, layout) = parse_cell(&mut rest, panes)?;
+    Some(layout)
+}
+
+// Parses one cell off the front of `rest`, returning its (width, height)
+fn parse_cell(rest: &mut &str, panes: &HashMap<String, Pane>) -> Option<((u32, u32), Layout)> {
+    let end = rest.find(',')?;
+    let (width, height) = rest[..end].split_once('x')?;
+    let size = (width.parse().ok()?, height.parse().ok()?);
+    *rest = &rest[end + 1..];
+    // The offsets: X up to a comma, Y up to whatever comes next
+    let end = rest.find(',')?;
+    *rest = &rest[end + 1..];
+    let end = rest.find([',', '{', '[', '}', ']']).unwrap_or(rest.len());
+    *rest = &rest[end..];
+    match rest.chars().next() {
+        Some(open @ ('{' | '[')) => {
+            let close = if open == '{' { '}' } else { ']' };
+            let vertical = open == '{';
+            *rest = &rest[1..];
+            let mut children = Vec::new();
+            loop {
+                let ((width, height), child) = parse_cell(rest, panes)?;
+                children.push((if vertical { width } else { height }, child));
+                match rest.chars().next()? {
+                    ',' => *rest = &rest[1..],
+                    c if c == close => {
+                        *rest = &rest[1..];
+                        break;
+                    }
+                    _ => return None,
+                }
+            }
+            Some((size, Layout::Split { vertical, children }))
+        }
+        Some(',') => {
+            *rest = &rest[1..];
+            let end = rest.find([',', '}', ']']).unwrap_or(rest.len());
+            let id = &rest[..end];
+            *rest = &rest[end..];
+            let pane = panes.get(id).cloned().unwrap_or_default();
+            Some((size, Layout::Pane(pane)))
+        }
+        _ => None,
+    }
+}
+
+// A KDL string
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn render_layout(layout: &Layout, size: Option<String>, depth: usize, out: &mut String) {
+    let indent = "    ".repeat(depth);
+    let size = size
+        .map(|size| format!(" size={}", quoted(&size)))
+        .unwrap_or_default();
+    match layout {
+        Layout::Pane(pane) => {
+            out.push_str(&format!("{}pane{}", indent, size));
+            if let Some(command) = &pane.command {
+                out.push_str(&format!(" command={}", quoted(command)));
+            }
+            if let Some(cwd) = &pane.cwd {
+                out.push_str(&format!(" cwd={}", quoted(cwd)));
+            }
+            out.push('\n');
+        }
+        Layout::Split { vertical, children } => {
+            let direction = if *vertical { "vertical" } else { "horizontal" };
+            out.push_str(&format!(
+                "{}pane{} split_direction=\"{}\" {{\n",
+                indent, size, direction
+            ));
+            let total: u32 = children.iter().map(|(size, _)| size).sum();
+            for (size, child) in children {
+                let percent = (*size as f64 / total.max(1) as f64 * 100.0).round();
+                render_layout(child, Some(format!("{}%", percent)), depth + 1, out);
+            }
+            out.push_str(&format!("{}}}\n", indent));
+        }
+    }
+}
+
+/// The session as a zellij layout, one tab per window
+pub fn to_layout(session: &Session) -> String {
+    let mut out = String::from("layout {\n");
+    for window in &session.windows {
+        out.push_str(&format!("    tab name={}", quoted(&window.name)));
+        if window.active {
+            out.push_str(" focus=true");
+        }
+        out.push_str(" {\n");
+        render_layout(&window.layout, None, 2, &mut out);
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preview;
+
+    fn panes() -> HashMap<String, Pane> {
+        let pane = |cwd: &str, command: Option<&str>| Pane {
+            cwd: Some(cwd.to_string()),
+            command: command.map(str::to_string),
+        };
+        HashMap::from([
+            ("1".to_string(), pane("/src", Some("vim"))),
+            ("2".to_string(), pane("/src", None)),
+            ("3".to_string(), pane("/logs", Some("tail -f app.log"))),
+            ("4".to_string(), pane("/", None)),
+        ])
+    }
+
+    fn pane(id: &str) -> Layout {
+        Layout::Pane(panes().remove(id).unwrap_or_default())
+    }
+
+    #[test]
+    fn a_window_with_one_pane() {
+        assert_eq!(parse_layout("c0d1,80x24,0,0,1", &panes()), Some(pane("1")));
+    }
+
+    #[test]
+    fn unknown_pane_ids_become_plain_panes() {
+        let layout = parse_layout("c0d1,80x24,0,0,99", &panes());
+        assert_eq!(layout, Some(Layout::Pane(Pane::default())));
+    }
+
+    #[test]
+    fn side_by_side_and_stacked_splits_nest() {
+        let layout = parse_layout(
+            "b25d,159x48,0,0{79x48,0,0,1,79x48,80,0[79x24,80,0,2,79x23,80,25,3]}",
+            &panes(),
+        );
+        let expected = Layout::Split {
+            vertical: true,
+            children: vec![
+                (79, pane("1")),
+                (
+                    79,
+                    Layout::Split {
+                        vertical: false,
+                        children: vec![(24, pane("2")), (23, pane("3"))],
+                    },
+                ),
+            ],
+        };
+        assert_eq!(layout, Some(expected));
+    }
+
+    #[test]
+    fn splits_nest_the_other_way_round_too() {
+        let layout = parse_layout(
+            "a1b2,80x40,0,0[80x20,0,0{40x20,0,0,1,39x20,41,0,2},80x19,0,21,4]",
+            &panes(),
+        );
+        let expected = Layout::Split {
+            vertical: false,
+            children: vec![
+                (
+                    20,
+                    Layout::Split {
+                        vertical: true,
+                        children: vec![(40, pane("1")), (39, pane("2"))],
+                    },
+                ),
+                (19, pane("4")),
+            ],
+        };
+        assert_eq!(layout, Some(expected));
+    }
+
+    #[test]
+    fn malformed_layouts_are_rejected() {
+        for malformed in [
+            "",
+            "c0d1",
+            "c0d1,80x24",
+            "c0d1,80x24,0",
+            "c0d1,80by24,0,0,1",
+            "c0d1,80x24,0,0",
+            "c0d1,wx24,0,0,1",
+            // Unclosed, mismatched and empty splits
+            "b25d,159x48,0,0{79x48,0,0,1,79x48,80,0,2",
+            "b25d,159x48,0,0{79x48,0,0,1,79x48,80,0,2]",
+            "b25d,159x48,0,0{}",
+            "b25d,159x48,0,0{79x48,0,0,1;79x48,80,0,2}",
+        ] {
+            assert_eq!(parse_layout(malformed, &panes()), None, "{:?}", malformed);
+        }
+    }
+
+    #[test]
+    fn windows_become_tabs_with_sizes_as_percentages() {
+        let session = Session {
+            windows: vec![
+                Window {
+                    name: "edit".to_string(),
+                    active: false,
+                    layout: parse_layout(
+                        "b25d,159x48,0,0{79x48,0,0,1,79x48,80,0[79x24,80,0,2,79x23,80,25,3]}",
+                        &panes(),
+                    )
+                    .unwrap(),
+                },
+                Window {
+                    name: "say \"hi\"".to_string(),
+                    active: true,
+                    layout: pane("4"),
+                },
+            ],
+        };
+        let layout = to_layout(&session);
+        assert_eq!(
+            layout,
+            r#"layout {
+    tab name="edit" {
+        pane split_direction="vertical" {
+            pane size="50%" command="vim" cwd="/src"
+            pane size="50%" split_direction="horizontal" {
+                pane size="51%" cwd="/src"
+                pane size="49%" command="tail -f app.log" cwd="/logs"
+            }
+        }
+    }
+    tab name="say \"hi\"" focus=true {
+        pane cwd="/"
+    }
+}
+"#
+        );
+        // What the picker's preview makes of it
+        let tabs = preview::summarize(&layout);
+        assert_eq!(tabs.len(), 2);
+        assert_eq!(tabs[0].panes, 3);
+        assert_eq!(tabs[0].commands, ["vim", "tail -f app.log"]);
+        assert!(tabs[1].focused);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::listing::{self, Filter, SortKey};
+use crate::locale;
+use crate::procfs::{self, Snapshot, Usage};
+use crate::sessions;
+use crate::state::State;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct Row {
+    session: String,
+    usage: Option<Usage>,
+    // Percent of one core since the last refresh
+    cpu: Option<f64>,
+}
+
+/// Shows the running sessions by resource usage, refreshing every
+/// `interval` until interrupted (or just once)
+pub fn run(
+    socket_dir: &Path,
+    state: &State,
+    filter: Option<&Filter>,
+    sort: Option<SortKey>,
+    interval: Duration,
+    once: bool,
+) -> io::Result<()> {
+    // CPU percentages need a previous sample, so take one a moment early
+    let first = sample(socket_dir, &Snapshot::take());
+    let mut previous = (Instant::now(), first);
+    thread::sleep(Duration::from_millis(250).min(interval));
+    loop {
+        let snapshot = Snapshot::take();
+        let now = Instant::now();
+        let elapsed = now.duration_since(previous.0).as_secs_f64();
+        // Cpu and Memory are sorted by below, from the same snapshot
+        let order = sort.filter(|key| !matches!(key, SortKey::Cpu | SortKey::Memory));
+        let running = sessions::get_sessions(socket_dir).unwrap_or_default();
+        let mut rows: Vec<Row> = listing::arrange(running, filter, order, socket_dir, state)
+            .into_iter()
+            .map(|session| {
+                let usage = snapshot.session_usage(&socket_dir.join(&session));
+                let cpu = usage
+                    .zip(previous.1.get(&session))
+                    .and_then(|(usage, before)| {
+                        let ticks = usage.cpu_ticks.saturating_sub(*before) as f64;
+                        (elapsed > 0.0)
+                            .then(|| ticks / procfs::TICKS_PER_SEC as f64 / elapsed * 100.0)
+                    });
+                Row {
+                    session,
+                    usage,
+                    cpu,
+                }
+            })
+            .collect();
+        match sort {
+            None | Some(SortKey::Cpu) => rows.sort_by(|a, b| {
+                let cpu = |row: &Row| row.cpu.unwrap_or(-1.0);
+                cpu(b).total_cmp(&cpu(a))
+            }),
+            Some(SortKey::Memory) => {
+                rows.sort_by_key(|row| Reverse(row.usage.map(|usage| usage.rss_kib)))
+            }
+            Some(_) => (),
+        }
+
+        let mut out = io::stdout().lock();
+        if !once {
+            // Home the cursor and clear, like top does
+            write!(out, "\x1b[H\x1b[2J")?;
+            writeln!(
+                out,
+                "{} session(s), every {:?} (Ctrl-C to quit)\n",
+                locale::current().count(rows.len() as u64),
+                interval
+            )?;
+        }
+        print_rows(&mut out, &rows)?;
+        out.flush()?;
+        if once {
+            return Ok(());
+        }
+        previous = (
+            now,
+            rows.iter()
+                .filter_map(|row| Some((row.session.clone(), row.usage?.cpu_ticks)))
+                .collect(),
+        );
+        thread::sleep(interval);
+    }
+}
+
+fn sample(socket_dir: &Path, snapshot: &Snapshot) -> HashMap<String, u64> {
+    sessions::get_sessions(socket_dir)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|session| {
+            let usage = snapshot.session_usage(&socket_dir.join(&session))?;
+            Some((session, usage.cpu_ticks))
+        })
+        .collect()
+}
+
+fn print_rows(out: &mut impl Write, rows: &[Row]) -> io::Result<()> {
+    let width = rows
+        .iter()
+        .map(|row| row.session.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("SESSION".len());
+    writeln!(
+        out,
+        "{:<width$}  {:>6}  {:>6}  {:>5}  {:>8}",
+        "SESSION",
+        "CPU%",
+        "MEM",
+        "PROCS",
+        "CPU TIME",
+        width = width
+    )?;
+    for row in rows {
+        let cpu = row
+            .cpu
+            .map(|cpu| locale::current().decimal(cpu, 1))
+            .unwrap_or_else(|| "-".to_string());
+        // A server we can't see in /proc, e.g. another user's
+        let (mem, procs, time) = match row.usage {
+            Some(usage) => (
+                procfs::format_kib(usage.rss_kib),
+                locale::current().count(usage.processes as u64),
+                procfs::format_ticks(usage.cpu_ticks),
+            ),
+            None => ("-".to_string(), "-".to_string(), "-".to_string()),
+        };
+        writeln!(
+            out,
+            "{:<width$}  {:>6}  {:>6}  {:>5}  {:>8}",
+            row.session,
+            cpu,
+            mem,
+            procs,
+            time,
+            width = width
+        )?;
+    }
+    Ok(())
+}
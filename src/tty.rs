@@ -0,0 +1,80 @@
+use nix::libc;
+use nix::pty::{openpty, Winsize};
+use nix::unistd::dup2;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Read};
+use std::os::unix::io::{AsFd, AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command};
+use std::thread;
+
+// The controlling terminal, whatever stdin and stdout were redirected to
+fn open_tty() -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).open("/dev/tty")
+}
+
+/// Reads session names, one per line, from stdin, then points stdin at the
+/// terminal so the picker can still prompt
+pub fn take_stdin_names() -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let name = line.trim();
+        if !name.is_empty() && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    // Without a terminal, the picker lists the names rather than prompting
+    if let Ok(tty) = open_tty() {
+        dup2(tty.as_raw_fd(), io::stdin().as_raw_fd()).map_err(io::Error::from)?;
+    }
+    Ok(names)
+}
+
+/// Points stdout at the terminal, so the picker is drawn there even when
+/// our output is being captured, and hands back the original stdout for
+/// printing the result to
+pub fn divert_stdout() -> io::Result<File> {
+    let original = File::from(io::stdout().as_fd().try_clone_to_owned()?);
+    if let Ok(tty) = open_tty() {
+        dup2(tty.as_raw_fd(), io::stdout().as_raw_fd()).map_err(io::Error::from)?;
+    }
+    Ok(original)
+}
+
+/// Runs `cmd` on a pseudo-terminal nobody looks at, for a zellij client
+/// that won't start without a terminal. What it draws is read and thrown
+/// away, so it never blocks on a full one.
+pub fn spawn_headless(cmd: &mut Command) -> io::Result<Child> {
+    let size = Winsize {
+        ws_row: 24,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let pty = openpty(&size, None).map_err(io::Error::from)?;
+    // Safe: openpty just opened these, and nothing else owns them
+    let (mut master, slave) =
+        unsafe { (File::from_raw_fd(pty.master), File::from_raw_fd(pty.slave)) };
+    cmd.stdin(slave.try_clone()?)
+        .stdout(slave.try_clone()?)
+        .stderr(slave);
+    // Safe: setsid and ioctl are all that runs between fork and exec, and
+    // both are async-signal-safe
+    unsafe {
+        cmd.pre_exec(|| {
+            // Its own session, with the pty as its controlling terminal
+            if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = cmd.spawn()?;
+    // Until the client is gone and reading fails
+    thread::spawn(move || {
+        let mut screen = [0; 4096];
+        while matches!(master.read(&mut screen), Ok(read) if read > 0) {}
+    });
+    Ok(child)
+}
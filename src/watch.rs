@@ -0,0 +1,116 @@
+use crate::sessions;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Notices sessions starting and going away. inotify on the socket
+/// directory wakes it as soon as a socket comes or goes, where it's
+/// available; every `interval` it probes the sockets regardless, since a
+/// server that crashed leaves its socket behind.
+pub struct Watcher {
+    socket_dir: PathBuf,
+    inotify: Option<Inotify>,
+    interval: Duration,
+    probed: Instant,
+    running: Vec<String>,
+}
+
+fn sorted(mut sessions: Vec<String>) -> Vec<String> {
+    sessions.sort();
+    sessions
+}
+
+impl Watcher {
+    pub fn new(socket_dir: &Path, interval: Duration) -> Self {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .and_then(|inotify| {
+                let flags = AddWatchFlags::IN_CREATE
+                    | AddWatchFlags::IN_DELETE
+                    | AddWatchFlags::IN_MOVED_FROM
+                    | AddWatchFlags::IN_MOVED_TO;
+                match inotify.add_watch(socket_dir, flags) {
+                    Ok(_) => Ok(inotify),
+                    Err(err) => {
+                        let _ = nix::unistd::close(inotify.as_raw_fd());
+                        Err(err)
+                    }
+                }
+            })
+            .inspect_err(|err| log::debug!("Not watching with inotify: {}", err))
+            .ok();
+        Watcher {
+            socket_dir: socket_dir.to_path_buf(),
+            inotify,
+            interval,
+            probed: Instant::now(),
+            running: sorted(sessions::get_sessions(socket_dir).unwrap_or_default()),
+        }
+    }
+
+    /// The running sessions as of the last look, sorted
+    pub fn running(&self) -> &[String] {
+        &self.running
+    }
+
+    /// Waits up to `timeout` for the running sessions to change, returning
+    /// the ones that started and the ones that went away if they did
+    pub fn wait(&mut self, timeout: Duration) -> Option<(Vec<String>, Vec<String>)> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let now = Instant::now();
+            let due = self.probed + self.interval;
+            let woken = if now >= due {
+                true
+            } else {
+                self.sleep(deadline.min(due).saturating_duration_since(now))
+            };
+            if woken || Instant::now() >= due {
+                self.probed = Instant::now();
+                let running = sorted(sessions::get_sessions(&self.socket_dir).unwrap_or_default());
+                if running != self.running {
+                    let started = running
+                        .iter()
+                        .filter(|s| !self.running.contains(s))
+                        .cloned()
+                        .collect();
+                    let gone = self
+                        .running
+                        .iter()
+                        .filter(|s| !running.contains(s))
+                        .cloned()
+                        .collect();
+                    self.running = running;
+                    return Some((started, gone));
+                }
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+        }
+    }
+
+    // Sleeps for `duration`, or until inotify has something; whether it did
+    fn sleep(&self, duration: Duration) -> bool {
+        let Some(inotify) = self.inotify else {
+            thread::sleep(duration);
+            return false;
+        };
+        let mut fds = [PollFd::new(inotify.as_raw_fd(), PollFlags::POLLIN)];
+        let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+        match poll(&mut fds, millis) {
+            Ok(n) if n > 0 => inotify.read_events().is_ok_and(|events| !events.is_empty()),
+            _ => false,
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        if let Some(inotify) = self.inotify {
+            let _ = nix::unistd::close(inotify.as_raw_fd());
+        }
+    }
+}
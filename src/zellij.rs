@@ -0,0 +1,308 @@
+use crate::dryrun;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use zellij_utils::{consts::VERSION, envs::SOCKET_DIR_ENV_KEY};
+
+/// The zellij version whose IPC protocol we were built against
+pub const PROTOCOL_VERSION: &str = VERSION;
+
+/// A zellij version, as in `zellij --version`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(pub u32, pub u32, pub u32);
+
+impl Version {
+    /// "0.31.4", or None if that isn't what it looks like
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.trim().splitn(3, '.');
+        let mut next = || parts.next()?.parse().ok();
+        Some(Version(next()?, next()?, next()?))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// What the zellij CLI only learned to do after the version we speak the
+/// protocol of; with --ignore-version-mismatch, a newer zellij gets them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// `zellij action ...`, unstable before 0.32
+    Actions,
+    /// `zellij action go-to-tab-name`
+    GoToTabName,
+    /// `zellij attach --create-background`
+    CreateBackground,
+    /// `zellij action rename-session`
+    RenameSession,
+    /// `zellij action dump-layout` dumping the session's own layout rather
+    /// than the default one
+    DumpLayout,
+    /// Sessions serialized to the cache so they can be brought back after
+    /// they exit
+    Resurrection,
+}
+
+impl Feature {
+    pub fn since(self) -> Version {
+        match self {
+            Feature::Actions => Version(0, 32, 0),
+            Feature::GoToTabName => Version(0, 34, 0),
+            Feature::CreateBackground => Version(0, 39, 0),
+            Feature::RenameSession => Version(0, 38, 0),
+            Feature::DumpLayout => Version(0, 39, 0),
+            Feature::Resurrection => Version(0, 39, 0),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Feature::Actions => "`zellij action`",
+            Feature::GoToTabName => "going to a tab by name",
+            Feature::CreateBackground => "starting sessions in the background",
+            Feature::RenameSession => "renaming sessions",
+            Feature::DumpLayout => "reading a session's tabs and panes",
+            Feature::Resurrection => "resurrecting exited sessions",
+        }
+    }
+
+    /// Why `feature` can't be had from a zellij of `version` (None if
+    /// that's unknown), as an error of kind `Unsupported`
+    pub fn unsupported(self, version: Option<Version>) -> io::Error {
+        let this = version.map_or_else(|| "an unknown version".to_string(), |v| v.to_string());
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{} needs zellij {} or newer, and this is {}",
+                self.describe(),
+                self.since(),
+                this
+            ),
+        )
+    }
+}
+
+/// Everything needed to run the `zellij` binary the way the user wants it
+#[derive(Debug, Clone)]
+pub struct Zellij {
+    pub bin: PathBuf,
+    /// Whatever came after `--` on our command line; these go in front of
+    /// zellij's subcommand so global options like `--config` work
+    pub extra_args: Vec<OsString>,
+    /// A custom base socket directory, handed down so new servers put
+    /// their sockets where we'll look for them
+    pub socket_dir: Option<PathBuf>,
+    // `zellij --version`, once it's been run
+    version: OnceLock<String>,
+}
+
+impl Zellij {
+    pub fn new(bin: PathBuf, extra_args: Vec<OsString>, socket_dir: Option<PathBuf>) -> Self {
+        Self {
+            bin,
+            extra_args,
+            socket_dir,
+            version: OnceLock::new(),
+        }
+    }
+
+    pub fn command(&self) -> Command {
+        self.command_via(&[])
+    }
+
+    /// Like `command`, but running zellij through `wrapper` (e.g. `nice
+    /// -n 10`) when it isn't empty
+    pub fn command_via(&self, wrapper: &[String]) -> Command {
+        let mut cmd = match wrapper.split_first() {
+            Some((program, args)) => {
+                let mut cmd = Command::new(program);
+                cmd.args(args).arg(&self.bin);
+                cmd
+            }
+            None => Command::new(&self.bin),
+        };
+        cmd.args(&self.extra_args);
+        if let Some(socket_dir) = &self.socket_dir {
+            cmd.env(SOCKET_DIR_ENV_KEY, socket_dir);
+        }
+        cmd
+    }
+
+    /// Runs `zellij --version`, which doubles as a check that the binary
+    /// is really there and runs. Only the first time: after that it's
+    /// remembered.
+    pub fn version(&self) -> io::Result<String> {
+        if let Some(version) = self.version.get() {
+            return Ok(version.clone());
+        }
+        let output = Command::new(&self.bin)
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "`{} --version` exited with {}",
+                self.bin.display(),
+                output.status
+            )));
+        }
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(self.version.get_or_init(|| version).clone())
+    }
+
+    /// Just the version number out of `zellij --version` ("zellij 0.31.4")
+    pub fn version_number(&self) -> io::Result<String> {
+        let version = self.version()?;
+        Ok(version
+            .split_whitespace()
+            .last()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Whether the zellij we run can do `feature`; not if it doesn't run
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.parsed_version()
+            .is_some_and(|version| version >= feature.since())
+    }
+
+    /// Fails with a word on which zellij it'd take unless `supports`
+    pub fn require(&self, feature: Feature) -> io::Result<()> {
+        if self.supports(feature) {
+            return Ok(());
+        }
+        Err(feature.unsupported(self.parsed_version()))
+    }
+
+    fn parsed_version(&self) -> Option<Version> {
+        Version::parse(&self.version_number().ok()?)
+    }
+
+    // Runs `zellij --session <session> action ...` against a running session
+    fn action(&self, session: &str, args: &[&str]) -> io::Result<()> {
+        self.require(Feature::Actions)?;
+        if dryrun::pretend(format_args!(
+            "run `zellij action {}` on {}",
+            args.join(" "),
+            session
+        )) {
+            return Ok(());
+        }
+        log::debug!("Running zellij action {} on {:?}", args.join(" "), session);
+        let status = self
+            .command()
+            .arg("--session")
+            .arg(session)
+            .arg("action")
+            .args(args)
+            .stdin(Stdio::null())
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "`zellij action {}` exited with {}",
+                args.join(" "),
+                status
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn rename_session(&self, from: &str, to: &str) -> io::Result<()> {
+        self.require(Feature::RenameSession)?;
+        self.action(from, &["rename-session", to])
+    }
+
+    pub fn kill_session(&self, session: &str) -> io::Result<()> {
+        if dryrun::pretend(format_args!("kill {}", session)) {
+            return Ok(());
+        }
+        log::info!("Killing {:?}", session);
+        let status = self
+            .command()
+            .arg("kill-session")
+            .arg(session)
+            .stdin(Stdio::null())
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "`zellij kill-session` exited with {}",
+                status
+            )));
+        }
+        Ok(())
+    }
+
+    /// Opens a new tab laid out like `layout` (a KDL file)
+    pub fn new_tab(&self, session: &str, layout: &Path, name: &str) -> io::Result<()> {
+        let layout = layout.to_string_lossy();
+        self.action(session, &["new-tab", "--layout", &layout, "--name", name])
+    }
+
+    pub fn close_tab(&self, session: &str, name: &str) -> io::Result<()> {
+        self.require(Feature::GoToTabName)?;
+        self.action(session, &["go-to-tab-name", name])?;
+        self.action(session, &["close-tab"])
+    }
+
+    /// Types `chars` into the session's focused pane
+    pub fn write_chars(&self, session: &str, chars: &str) -> io::Result<()> {
+        self.action(session, &["write-chars", chars])
+    }
+
+    /// Focuses the session's tab at `position`, counting from 1
+    pub fn go_to_tab(&self, session: &str, position: usize) -> io::Result<()> {
+        self.action(session, &["go-to-tab", &position.to_string()])
+    }
+
+    /// The session's current tabs and panes, as KDL
+    pub fn dump_layout(&self, session: &str) -> io::Result<String> {
+        self.require(Feature::DumpLayout)?;
+        let output = self
+            .command()
+            .arg("--session")
+            .arg(session)
+            .args(["action", "dump-layout"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "`zellij action dump-layout` exited with {}",
+                output.status
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_by_number() {
+        assert_eq!(Version::parse("0.31.4"), Some(Version(0, 31, 4)));
+        assert!(Version::parse("0.39.2").unwrap() > Version::parse("0.9.10").unwrap());
+        assert_eq!(Version::parse("0.40"), None);
+        assert_eq!(Version::parse("nightly"), None);
+    }
+
+    #[test]
+    fn a_feature_comes_with_its_version() {
+        let zellij = Zellij::new(PathBuf::from("zellij"), Vec::new(), None);
+        let _ = zellij.version.set("zellij 0.31.4".to_string());
+        assert!(!zellij.supports(Feature::Actions));
+        let err = zellij.require(Feature::Actions).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        let zellij = Zellij::new(PathBuf::from("zellij"), Vec::new(), None);
+        let _ = zellij.version.set("zellij 0.40.1".to_string());
+        assert!(zellij.supports(Feature::GoToTabName));
+    }
+}